@@ -1,13 +1,16 @@
+// Dates are formatted in the language of the page
+const lang = document.documentElement.lang;
+
 function format_date(elements) {
     elements.forEach(e => {
-        e.innerHTML = dayjs(e.innerHTML).format("dddd MMMM D, YYYY");
+        e.innerHTML = new Date(e.innerHTML).toLocaleDateString(lang, { weekday: "long", year: "numeric", month: "long", day: "numeric" });
     });
 }
 format_date(document.querySelectorAll("[id=date]"));
 
 function format_date_long(elements) {
     elements.forEach(e => {
-        e.innerHTML = dayjs(e.innerHTML).format("HH:mm, dddd MMMM D, YYYY");
+        e.innerHTML = new Date(e.innerHTML).toLocaleString(lang, { hour: "2-digit", minute: "2-digit", weekday: "long", year: "numeric", month: "long", day: "numeric" });
     });
 }
 format_date_long(document.querySelectorAll("[id=date-long]"));
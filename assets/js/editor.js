@@ -0,0 +1,28 @@
+var editor = document.getElementById("editor");
+var preview = document.getElementById("preview");
+var timeout = null;
+
+// Renders the article through the same pipeline as published articles
+async function update_preview() {
+	var payload = new URLSearchParams({
+		"manifest": editor.elements["manifest"].value,
+		"content": editor.elements["content"].value,
+	});
+	var response = await fetch("/admin/preview", { method: "POST", body: payload });
+	var html = await response.text();
+	if (response.status === 200) {
+		preview.srcdoc = html;
+	} else {
+		preview.srcdoc = "<pre></pre>";
+		preview.onload = function() {
+			preview.contentDocument.querySelector("pre").textContent = html;
+			preview.onload = null;
+		};
+	}
+}
+
+editor.addEventListener("input", function() {
+	clearTimeout(timeout);
+	timeout = setTimeout(update_preview, 500);
+});
+update_preview();
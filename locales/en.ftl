@@ -0,0 +1,48 @@
+main-page = Main page
+articles = Articles
+legal = Legal
+discord-server = Discord Server
+rss-feed = RSS Feed
+search = Search
+search-placeholder = Search
+theme = Theme: { $theme ->
+    [light] light
+    [auto] automatic
+   *[dark] dark
+}
+
+newsletter-prompt = Subscribe to the newsletter to stay updated with my work!
+newsletter-email = Email
+newsletter-subscribe = Subscribe
+newsletter-notice = No spam and you can unsubscribe at any time. By subscribing, you accept the <a href="/legal#privacy" target="_blank">Privacy Policy</a>
+
+discuss-on-discord = Discuss this article on Discord
+contact-me = Contact me:
+share-prompt = Like my work? Share it! ❤️
+copy-link = Copy link
+share-on = Share on { $platform }
+license = This article is licensed under <b>{ $license }</b>
+mentions = Mentioned by
+react = React to this article
+sort-relevance = Most relevant
+sort-date = Most recent
+sort-popular = Most popular
+private = Private
+
+error-reference = reference: { $reference }
+error-not-found = This page does not exist.
+error-suggestions = Maybe you were looking for:
+error-too-many-requests = You are sending too many requests. Please try again later.
+error-retry-after = You are sending too many requests. Please try again in { $seconds } seconds.
+error-server = Something went wrong on our side. If the problem persists, please contact me with the reference below.
+
+admin-title = Administration
+admin-token = Token
+admin-login = Log in
+admin-editor = Articles editor
+admin-editor-new = New article slug
+admin-editor-manifest = Manifest
+admin-editor-content = Content
+admin-editor-message = Commit message
+admin-editor-save = Save and commit
+admin-editor-preview = Preview
@@ -0,0 +1,48 @@
+main-page = Page principale
+articles = Articles
+legal = Mentions légales
+discord-server = Serveur Discord
+rss-feed = Flux RSS
+search = Recherche
+search-placeholder = Rechercher
+theme = Thème : { $theme ->
+    [light] clair
+    [auto] automatique
+   *[dark] sombre
+}
+
+newsletter-prompt = Abonnez-vous à la newsletter pour suivre mon travail !
+newsletter-email = E-mail
+newsletter-subscribe = S'abonner
+newsletter-notice = Pas de spam, et vous pouvez vous désabonner à tout moment. En vous abonnant, vous acceptez la <a href="/legal#privacy" target="_blank">politique de confidentialité</a>
+
+discuss-on-discord = Discuter de cet article sur Discord
+contact-me = Me contacter :
+share-prompt = Vous aimez mon travail ? Partagez-le ! ❤️
+copy-link = Copier le lien
+share-on = Partager sur { $platform }
+license = Cet article est publié sous licence <b>{ $license }</b>
+react = Réagir à cet article
+sort-relevance = Les plus pertinents
+sort-date = Les plus récents
+sort-popular = Les plus populaires
+private = Privé
+mentions = Mentionné par
+
+error-reference = référence : { $reference }
+error-not-found = Cette page n'existe pas.
+error-suggestions = Vous cherchiez peut-être :
+error-too-many-requests = Vous envoyez trop de requêtes. Veuillez réessayer plus tard.
+error-retry-after = Vous envoyez trop de requêtes. Veuillez réessayer dans { $seconds } secondes.
+error-server = Une erreur est survenue de notre côté. Si le problème persiste, contactez-moi avec la référence ci-dessous.
+
+admin-title = Administration
+admin-token = Jeton
+admin-login = Se connecter
+admin-editor = Éditeur d'articles
+admin-editor-new = Slug du nouvel article
+admin-editor-manifest = Manifeste
+admin-editor-content = Contenu
+admin-editor-message = Message de commit
+admin-editor-save = Enregistrer et commiter
+admin-editor-preview = Aperçu
@@ -1,16 +1,320 @@
-use serde::Deserialize;
-use std::path::PathBuf;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{
+	collections::HashMap,
+	env, fs, io,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr},
+	path::PathBuf,
+};
+
+/// The prefix of environment variables holding the configuration.
+const ENV_PREFIX: &str = "BLOG_";
+/// The path to the configuration file, if not specified otherwise.
+const DEFAULT_PATH: &str = "config.toml";
 
 /// Server configuration.
-#[derive(Deserialize)]
+///
+/// Values are read from, by increasing priority:
+/// - the configuration file (`config.toml`, or the path given by `--config` or `BLOG_CONFIG`)
+/// - `BLOG_*` environment variables
+/// - `--<key>=<value>` command line flags
+#[derive(Deserialize, Serialize)]
 pub struct Config {
-	/// The HTTP server's port.
+	/// The HTTP server's port. Required to run the server.
+	#[serde(default)]
 	pub port: u16,
-	/// The URL to the Discord server's invitation.
+	/// The path to the TLS certificate chain, in PEM format. If set along with the key, the
+	/// server serves HTTPS directly.
+	#[cfg(feature = "tls")]
+	pub tls_cert_path: Option<PathBuf>,
+	/// The path to the TLS private key, in PEM format.
+	#[cfg(feature = "tls")]
+	pub tls_key_path: Option<PathBuf>,
+	/// The public URL of the blog, without trailing slash.
+	#[serde(default = "default_base_url")]
+	pub base_url: String,
+	/// The locale of the user interface when the client does not accept any available locale.
+	#[serde(default = "default_locale")]
+	pub default_locale: String,
+	/// The URL to the Discord server's invitation. Required to run the server.
+	#[serde(default)]
 	pub discord_invite: String,
+	/// The token granting access to administration features. If not set, administration is
+	/// disabled.
+	#[serde(serialize_with = "redact")]
+	pub admin_token: Option<String>,
+	/// Tokens granting access to the administration API, for scripts and other tools.
+	#[serde(default, serialize_with = "redact_all")]
+	pub admin_api_tokens: Vec<String>,
+
+	/// Addresses of the reverse proxies allowed to give the client's address in the
+	/// `X-Forwarded-For` header.
+	#[serde(default = "default_trusted_proxies")]
+	pub trusted_proxies: Vec<IpAddr>,
+
+	/// The maximum number of requests a client can burst on rate limited routes.
+	#[serde(default = "default_rate_limit_burst")]
+	pub rate_limit_burst: u32,
+	/// The number of requests per minute a client is allowed on rate limited routes.
+	#[serde(default = "default_rate_limit_per_minute")]
+	pub rate_limit_per_minute: u32,
+	/// Addresses that are not subject to rate limiting.
+	#[serde(default)]
+	pub rate_limit_allowlist: Vec<IpAddr>,
+
+	/// The duration, in seconds, for which clients may cache images, fonts and videos.
+	#[serde(default = "default_media_max_age")]
+	pub media_max_age: u64,
+	/// The duration, in seconds, for which clients may cache other assets, such as CSS and
+	/// JavaScript.
+	#[serde(default = "default_asset_max_age")]
+	pub asset_max_age: u64,
+
+	/// The maximum number of rendered pages kept in memory.
+	#[serde(default = "default_page_cache_size")]
+	pub page_cache_size: usize,
+
+	/// The path to the directory where resized images are cached. Defaults to a directory in
+	/// the system's temporary directory.
+	pub image_cache_path: Option<PathBuf>,
 
 	/// The path to articles.
 	pub article_path: PathBuf,
+	/// The path to the directory where data collected while running is persisted. Defaults to
+	/// the article directory.
+	pub data_path: Option<PathBuf>,
 	/// The path to article assets.
 	pub article_assets_path: PathBuf,
+	/// The maximum size of an uploaded file, in bytes.
+	#[serde(default = "default_upload_max_size")]
+	pub upload_max_size: usize,
+	/// The maximum size of the body of other requests, in bytes.
+	#[serde(default = "default_body_max_size")]
+	pub body_max_size: usize,
+	/// The duration, in seconds, after which the handling of a request is aborted.
+	#[serde(default = "default_request_timeout")]
+	pub request_timeout: u64,
+	/// The duration, in seconds, after which the handling of a file upload is aborted.
+	#[serde(default = "default_upload_timeout")]
+	pub upload_timeout: u64,
+	/// The license applied to articles that do not specify one.
+	pub default_license: Option<String>,
+
+	/// If `true`, clicks on external links in articles are tracked.
+	#[serde(default)]
+	pub outbound_tracking: bool,
+	/// Domains the outbound click tracker can redirect to, even if not linked in an article.
+	#[serde(default)]
+	pub outbound_allowlist: Vec<String>,
+	/// Domains for which outbound clicks are never tracked.
+	#[serde(default)]
+	pub outbound_denylist: Vec<String>,
+
+	/// Tells whether webmentions are sent for new articles and received.
+	#[serde(default)]
+	pub webmentions: bool,
+
+	/// The URL of the webhook receiving reports of server errors, as JSON.
+	#[serde(serialize_with = "redact")]
+	pub error_webhook: Option<String>,
+	/// The URLs of the webhooks notified of events on the blog.
+	#[serde(default)]
+	pub webhook_urls: Vec<String>,
+	/// The secret used to sign requests to the webhooks.
+	#[serde(serialize_with = "redact")]
+	pub webhook_secret: Option<String>,
+}
+
+impl Config {
+	/// Loads the configuration from all sources, using the given command line arguments.
+	///
+	/// Settings that are only used by the server are not required for the `lint` subcommand.
+	pub fn load(args: &[String]) -> Result<Self> {
+		let mut values = HashMap::new();
+		// Configuration file
+		let flag_path = args.iter().find_map(|a| a.strip_prefix("--config="));
+		let env_path = env::var(format!("{ENV_PREFIX}CONFIG")).ok();
+		let path = flag_path.or(env_path.as_deref());
+		let content = match fs::read_to_string(path.unwrap_or(DEFAULT_PATH)) {
+			Ok(content) => Some(content),
+			// The default file is optional
+			Err(e) if path.is_none() && e.kind() == io::ErrorKind::NotFound => None,
+			Err(e) => bail!("cannot read configuration file: {e}"),
+		};
+		if let Some(content) = content {
+			let table: toml::Table = toml::from_str(&content)?;
+			for (key, value) in table {
+				let value = match value {
+					toml::Value::String(s) => s,
+					toml::Value::Array(values) => values
+						.into_iter()
+						.map(|v| match v {
+							toml::Value::String(s) => s,
+							v => v.to_string(),
+						})
+						.collect::<Vec<_>>()
+						.join(","),
+					toml::Value::Table(_) => bail!("invalid value for `{key}`: unexpected table"),
+					v => v.to_string(),
+				};
+				values.insert(key, value);
+			}
+		}
+		// Environment
+		let vars = env::vars().filter_map(|(key, value)| {
+			let key = key.strip_prefix(ENV_PREFIX)?.to_lowercase();
+			Some((key, value))
+		});
+		values.extend(vars);
+		// Command line
+		let flags = args.iter().filter_map(|a| {
+			let (key, value) = a.strip_prefix("--")?.split_once('=')?;
+			Some((key.replace('-', "_"), value.to_owned()))
+		});
+		values.extend(flags);
+		values.remove("config");
+		let mut config: Self = envy::from_iter(values)?;
+		config.base_url = config.base_url.trim_end_matches('/').to_owned();
+		let serve = args.first().map(String::as_str) != Some("lint");
+		config.validate(serve)?;
+		Ok(config)
+	}
+
+	/// Checks the configuration is consistent.
+	///
+	/// If `serve` is `true`, settings required to run the server are checked too.
+	fn validate(&self, serve: bool) -> Result<()> {
+		let mut errors = vec![];
+		if serve && self.port == 0 {
+			errors.push("`port` must be set to a non-zero value".to_owned());
+		}
+		if serve && self.discord_invite.is_empty() {
+			errors.push("`discord_invite` must be set".to_owned());
+		}
+		if !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+			errors.push("`base_url` must be an HTTP(S) URL".to_owned());
+		}
+		#[cfg(feature = "tls")]
+		if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+			errors.push("`tls_cert_path` and `tls_key_path` must be set together".to_owned());
+		}
+		if self.admin_token.as_ref().is_some_and(|t| t.is_empty()) {
+			errors.push("`admin_token` must not be empty".to_owned());
+		}
+		if self.admin_api_tokens.iter().any(|t| t.is_empty()) {
+			errors.push("`admin_api_tokens` must not contain empty tokens".to_owned());
+		}
+		if self.rate_limit_burst == 0 || self.rate_limit_per_minute == 0 {
+			errors.push("rate limits must not be zero".to_owned());
+		}
+		if self.body_max_size == 0 || self.upload_max_size == 0 {
+			errors.push("body size limits must not be zero".to_owned());
+		}
+		if self.request_timeout == 0 || self.upload_timeout == 0 {
+			errors.push("timeouts must not be zero".to_owned());
+		}
+		if !self.article_path.is_dir() {
+			errors.push(format!(
+				"`article_path` is not a directory: {}",
+				self.article_path.display()
+			));
+		}
+		if let Some(path) = self.data_path.as_ref().filter(|p| !p.is_dir()) {
+			errors.push(format!(
+				"`data_path` is not a directory: {}",
+				path.display()
+			));
+		}
+		if !self.article_assets_path.is_dir() {
+			errors.push(format!(
+				"`article_assets_path` is not a directory: {}",
+				self.article_assets_path.display()
+			));
+		}
+		let webhook = self.error_webhook.as_deref();
+		if webhook.is_some_and(|url| !url.starts_with("http://") && !url.starts_with("https://")) {
+			errors.push("`error_webhook` must be an HTTP(S) URL".to_owned());
+		}
+		let is_url = |url: &String| url.starts_with("http://") || url.starts_with("https://");
+		if !self.webhook_urls.iter().all(is_url) {
+			errors.push("`webhook_urls` must only contain HTTP(S) URLs".to_owned());
+		}
+		let secret = self.webhook_secret.as_deref().unwrap_or_default();
+		if !self.webhook_urls.is_empty() && secret.is_empty() {
+			errors.push("`webhook_secret` must be set when `webhook_urls` is".to_owned());
+		}
+		if !errors.is_empty() {
+			bail!("{}", errors.join("; "));
+		}
+		Ok(())
+	}
+
+	/// Returns the effective configuration in TOML format, with secrets redacted.
+	pub fn to_redacted_string(&self) -> String {
+		toml::to_string(self).unwrap()
+	}
+}
+
+/// Serializes a secret without revealing it.
+fn redact<S: Serializer>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+	match value {
+		Some(_) => serializer.serialize_str("<redacted>"),
+		None => serializer.serialize_none(),
+	}
+}
+
+/// Serializes a list of secrets without revealing them.
+fn redact_all<S: Serializer>(values: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.collect_seq(values.iter().map(|_| "<redacted>"))
+}
+
+fn default_base_url() -> String {
+	"https://blog.lenot.re".to_owned()
+}
+
+fn default_locale() -> String {
+	"en".to_owned()
+}
+
+fn default_trusted_proxies() -> Vec<IpAddr> {
+	vec![
+		IpAddr::V4(Ipv4Addr::LOCALHOST),
+		IpAddr::V6(Ipv6Addr::LOCALHOST),
+	]
+}
+
+fn default_rate_limit_burst() -> u32 {
+	20
+}
+
+fn default_rate_limit_per_minute() -> u32 {
+	60
+}
+
+fn default_media_max_age() -> u64 {
+	30 * 24 * 60 * 60
+}
+
+fn default_asset_max_age() -> u64 {
+	60 * 60
+}
+
+fn default_page_cache_size() -> usize {
+	256
+}
+
+fn default_upload_max_size() -> usize {
+	10 * 1024 * 1024
+}
+
+fn default_body_max_size() -> usize {
+	64 * 1024
+}
+
+fn default_request_timeout() -> u64 {
+	30
+}
+
+fn default_upload_timeout() -> u64 {
+	300
 }
@@ -1,32 +1,138 @@
 mod config;
+mod middleware;
 mod route;
 mod service;
+mod template;
+mod util;
 
-use crate::service::article::Article;
+use crate::{
+	middleware::PanicMessage,
+	service::{
+		article::{Article, ArticleQuery, ArticleSort, Author},
+		feed::FeedSubscribers,
+		graphql::{self, BlogSchema},
+		i18n::Translations,
+		lint,
+		live::LiveVisitors,
+		not_found::NotFoundLog,
+		og,
+		outbound::OutboundTracker,
+		page_cache::PageCache,
+		publication,
+		rate_limit::RateLimiter,
+		reaction::Reactions,
+		report::ErrorReporter,
+		search::SearchIndex,
+		webhook::Webhooks,
+		webmention::Webmentions,
+	},
+};
 use axum::{
-	extract::State,
-	http::StatusCode,
-	response::{Html, IntoResponse, Redirect, Response},
-	routing::get,
+	body::Bytes,
+	extract::{DefaultBodyLimit, State},
+	http::{header::CONTENT_TYPE, Method, StatusCode},
+	middleware::{from_fn, from_fn_with_state},
+	response::{IntoResponse, Redirect, Response},
+	routing::{delete, get, post},
 	Router,
 };
+use chrono::{DateTime, Utc};
 use config::Config;
 use gateway_api::log::LogLayer;
-use std::{collections::HashMap, io, net::SocketAddr, process::exit, sync::Arc};
-use tower_http::services::ServeDir;
-use tracing::{error, info};
+use std::{
+	cmp::Reverse,
+	collections::HashMap,
+	env, fs, io,
+	net::{IpAddr, SocketAddr},
+	path::PathBuf,
+	process::exit,
+	sync::Arc,
+	time::Duration,
+};
+use tower_http::{
+	catch_panic::CatchPanicLayer,
+	cors::{Any, CorsLayer},
+	services::ServeDir,
+};
+use tracing::{debug, error, info};
+
+/// The maximum number of articles suggested on the `404 Not Found` page.
+const MAX_SUGGESTIONS: usize = 3;
+/// The minimum similarity, between `0` and `1`, of an article's slug to the requested path for
+/// the article to be suggested.
+const MIN_SUGGESTION_SCORE: f64 = 0.5;
 
 /// Structure shared across the server.
 pub struct Context {
 	/// Configuration of the gateway API.
 	pub gateway_config: &'static gateway_api::Config,
 
+	/// The public URL of the blog, without trailing slash.
+	pub base_url: String,
 	/// The URL to the Discord server's invitation.
 	pub discord_invite: String,
+	/// The token granting access to administration features.
+	pub admin_token: Option<String>,
+	/// Tokens granting access to the administration API.
+	pub admin_api_tokens: Vec<String>,
+	/// Addresses of the reverse proxies trusted to give the client's address.
+	pub trusted_proxies: Vec<IpAddr>,
+	/// The license applied to articles that do not specify one.
+	pub default_license: Option<String>,
+	/// The path to the articles repository.
+	pub articles_path: PathBuf,
+	/// The path to article assets.
+	pub assets_path: PathBuf,
+	/// The path to the cache of resized images.
+	pub image_cache_path: PathBuf,
+	/// The path to the directory where data collected while running is persisted.
+	pub data_path: PathBuf,
+	/// The date at which articles have been compiled.
+	pub compile_date: DateTime<Utc>,
 	/// Articles along with their respective compiled content, ordered by post date.
 	pub articles: Vec<(Article, String)>,
 	/// A map to find an article index from its slug.
 	pub articles_index: HashMap<String, usize>,
+	/// A map to find an article index from its legacy identifier.
+	pub legacy_index: HashMap<String, usize>,
+	/// The duration after which the handling of a request is aborted.
+	pub request_timeout: Duration,
+	/// The duration after which the handling of a file upload is aborted.
+	pub upload_timeout: Duration,
+	/// The duration, in seconds, for which clients may cache images, fonts and videos.
+	pub media_max_age: u64,
+	/// The duration, in seconds, for which clients may cache other assets.
+	pub asset_max_age: u64,
+	/// The rate limiter for expensive or abuse-prone routes.
+	pub rate_limiter: RateLimiter,
+	/// The counter of visitors currently reading the blog.
+	pub live_visitors: LiveVisitors,
+	/// The estimation of the number of subscribers to the RSS feed.
+	pub feed_subscribers: FeedSubscribers,
+	/// The tracker of clicks on external links.
+	pub outbound: OutboundTracker,
+	/// The log of requests to pages that do not exist.
+	pub not_found: NotFoundLog,
+	/// The reporter of server errors.
+	pub error_reporter: ErrorReporter,
+	/// The webhooks notified of events.
+	pub webhooks: Webhooks,
+	/// The sender and receiver of webmentions.
+	pub webmentions: Webmentions,
+	/// Reactions to articles.
+	pub reactions: Reactions,
+	/// The HTTP client used to issue requests to other services.
+	pub http_client: reqwest::Client,
+	/// The translations of the user interface.
+	pub translations: Translations,
+	/// The full-text index of articles.
+	pub search_index: SearchIndex,
+	/// The cache of rendered pages.
+	pub page_cache: PageCache,
+	/// The schema of the GraphQL API.
+	pub graphql_schema: BlogSchema,
+	/// Generated preview images, in PNG format, for articles without a cover, by slug.
+	pub og_images: HashMap<String, Bytes>,
 }
 
 impl Context {
@@ -36,51 +142,241 @@ impl Context {
 		Some(&self.articles[index])
 	}
 
-	/// Returns the list of articles without their content.
-	pub fn list_articles(&self) -> impl Iterator<Item = &Article> {
-		self.articles.iter().map(|(a, _)| a)
+	/// Returns the article with the given legacy identifier.
+	pub fn get_legacy_article(&self, id: &str) -> Option<&Article> {
+		let index = *self.legacy_index.get(id)?;
+		Some(&self.articles[index].0)
+	}
+
+	/// Returns the author with the given slug.
+	pub fn get_author(&self, slug: &str) -> Option<&Author> {
+		self.articles
+			.iter()
+			.flat_map(|(a, _)| &a.authors)
+			.find(|a| a.get_slug() == slug)
+	}
+
+	/// Returns the public articles whose slug is the closest to the last segment of `path`, from
+	/// the closest to the farthest.
+	pub fn suggest_articles(&self, path: &str) -> Vec<&Article> {
+		let segment = path
+			.trim_end_matches('/')
+			.rsplit('/')
+			.next()
+			.unwrap_or_default()
+			.trim_end_matches(".md")
+			.to_lowercase();
+		if segment.is_empty() {
+			return vec![];
+		}
+		let mut suggestions: Vec<_> = self
+			.articles
+			.iter()
+			.map(|(a, _)| a)
+			.filter(|a| a.is_public())
+			.map(|a| (strsim::normalized_levenshtein(&segment, &a.slug), a))
+			.filter(|(score, _)| *score >= MIN_SUGGESTION_SCORE)
+			.collect();
+		suggestions.sort_unstable_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
+		suggestions
+			.into_iter()
+			.take(MAX_SUGGESTIONS)
+			.map(|(_, a)| a)
+			.collect()
+	}
+
+	/// Returns the list of articles matching the given query, without their content, in the
+	/// query's order.
+	pub fn query_articles<'a>(
+		&'a self,
+		query: &'a ArticleQuery,
+	) -> impl Iterator<Item = &'a Article> + 'a {
+		let mut articles: Vec<_> = self
+			.articles
+			.iter()
+			.filter(|(a, content)| query.matches(a, content))
+			.map(|(a, _)| a)
+			.collect();
+		if query.sort == ArticleSort::Popular {
+			articles.sort_by_cached_key(|a| Reverse(self.reactions.total(&a.slug)));
+		}
+		articles.into_iter()
 	}
 }
 
-async fn handle_404() -> Response {
-	let html = include_str!("../pages/error.html");
-	let status = StatusCode::NOT_FOUND;
-	let html = html.replace("{error.code}", &status.as_u16().to_string());
-	let html = html.replace("{error.reason}", status.canonical_reason().unwrap());
-	(status, Html(html)).into_response()
+/// The page is rendered by [`middleware::error_pages`].
+async fn handle_404() -> StatusCode {
+	StatusCode::NOT_FOUND
+}
+
+fn handle_panic(err: Box<dyn std::any::Any + Send + 'static>) -> Response {
+	let msg = err
+		.downcast_ref::<&str>()
+		.map(|s| s.to_string())
+		.or_else(|| err.downcast_ref::<String>().cloned())
+		.unwrap_or_else(|| "unknown panic".to_owned());
+	let mut response = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+	response.extensions_mut().insert(PanicMessage(msg));
+	response
 }
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
 	tracing_subscriber::fmt::init();
-	let config = envy::prefixed("BLOG_")
-		.from_env::<Config>()
-		.unwrap_or_else(|error| {
-			error!(%error, "invalid configuration");
-			exit(1);
-		});
+	let args: Vec<String> = env::args().skip(1).collect();
+	let config = Config::load(&args).unwrap_or_else(|error| {
+		error!(%error, "invalid configuration");
+		exit(1);
+	});
+	if args.iter().any(|a| a == "--check-config") {
+		print!("{}", config.to_redacted_string());
+		exit(0);
+	}
+	debug!("configuration:\n{}", config.to_redacted_string());
+	if args.first().map(String::as_str) == Some("lint") {
+		match lint::run(&config) {
+			Ok(true) => exit(0),
+			Ok(false) => exit(1),
+			Err(error) => {
+				error!(%error, "could not check articles");
+				exit(1);
+			}
+		}
+	}
 	info!("compile all articles");
-	let articles = Article::compile_all(&config.article_path).unwrap_or_else(|error| {
+	let compile_date = Utc::now();
+	let outbound = OutboundTracker::new(&config);
+	let mut articles = Article::compile_all(&config, &outbound).unwrap_or_else(|error| {
 		error!(%error, "could not compile articles");
 		exit(1);
 	});
+	let domain = config.base_url.split_once("://").map_or("", |(_, d)| d);
+	let mut og_images = HashMap::new();
+	for (article, _) in articles.iter_mut().filter(|(a, _)| a.cover_url.is_empty()) {
+		match og::generate(article, domain) {
+			Ok(image) => {
+				// Absolute since it is used in Open Graph metadata
+				article.cover_url = format!("{}/og/{}.png", config.base_url, article.slug);
+				og_images.insert(article.slug.clone(), image.into());
+			}
+			Err(error) => error!(%error, slug = article.slug, "could not generate preview image"),
+		}
+	}
 	let articles_index = articles
 		.iter()
 		.enumerate()
 		.map(|(i, (a, _))| (a.slug.clone(), i))
 		.collect();
+	let legacy_index = articles
+		.iter()
+		.enumerate()
+		.filter_map(|(i, (a, _))| Some((a.legacy_id.clone()?, i)))
+		.collect();
 	info!("{} articles found", articles.len());
+	let search_index = SearchIndex::new(articles.iter().map(|(a, _)| a)).unwrap_or_else(|error| {
+		error!(%error, "could not build search index");
+		exit(1);
+	});
+	let image_cache_path = config
+		.image_cache_path
+		.clone()
+		.unwrap_or_else(|| env::temp_dir().join("blog-images"));
+	if let Err(error) = fs::create_dir_all(&image_cache_path) {
+		error!(%error, "could not create image cache directory");
+		exit(1);
+	}
+	let data_path = config
+		.data_path
+		.clone()
+		.unwrap_or_else(|| config.article_path.clone());
+	let translations = Translations::new(&config.default_locale).unwrap_or_else(|error| {
+		error!(%error, "could not load translations");
+		exit(1);
+	});
+	let http_client = reqwest::Client::new();
+	let rate_limiter = RateLimiter::new(
+		config.rate_limit_burst,
+		config.rate_limit_per_minute,
+		config.rate_limit_allowlist,
+	);
 	let ctx = Arc::new(Context {
 		gateway_config: gateway_api::Config::get(),
 
+		base_url: config.base_url,
 		discord_invite: config.discord_invite,
+		admin_token: config.admin_token,
+		admin_api_tokens: config.admin_api_tokens,
+		trusted_proxies: config.trusted_proxies,
+		default_license: config.default_license,
+		articles_path: config.article_path.clone(),
+		assets_path: config.article_assets_path.clone(),
+		image_cache_path,
+		data_path: data_path.clone(),
+		compile_date,
 		articles,
 		articles_index,
+		legacy_index,
+		og_images,
+		search_index,
+		page_cache: PageCache::new(config.page_cache_size),
+		graphql_schema: graphql::build_schema(),
+		translations,
+		request_timeout: Duration::from_secs(config.request_timeout),
+		upload_timeout: Duration::from_secs(config.upload_timeout),
+		media_max_age: config.media_max_age,
+		asset_max_age: config.asset_max_age,
+		rate_limiter,
+		live_visitors: LiveVisitors::new(Duration::from_secs(5 * 60)),
+		not_found: NotFoundLog::default(),
+		feed_subscribers: FeedSubscribers::default(),
+		outbound,
+		error_reporter: ErrorReporter::new(http_client.clone(), config.error_webhook),
+		webhooks: Webhooks::new(
+			http_client.clone(),
+			config.webhook_urls,
+			config.webhook_secret,
+		),
+		webmentions: Webmentions::new(config.webmentions, &data_path),
+		reactions: Reactions::new(&data_path),
+		http_client,
 	});
+	if ctx.webhooks.is_enabled() || ctx.webmentions.is_enabled() {
+		tokio::spawn(publication::watch(ctx.clone()));
+	}
 	info!("start http server");
-	let router = Router::new()
+	let admin_api = Router::new()
+		.route("/openapi.json", get(route::admin::openapi))
+		.route("/drafts", get(route::admin::drafts))
+		.route("/live", get(route::admin::live))
+		.route("/not-found", get(route::admin::not_found))
+		.route("/feed-subscribers", get(route::admin::feed_subscribers))
+		.route("/outbound", get(route::admin::outbound))
+		.route("/webhooks", get(route::admin::webhooks))
+		.route("/webmentions", get(route::admin::webmentions))
+		.route(
+			"/files",
+			get(route::admin::list_files)
+				.post(route::admin::upload_file)
+				.layer(DefaultBodyLimit::max(config.upload_max_size)),
+		)
+		.route("/files/*path", delete(route::admin::delete_file));
+	let api = Router::new()
+		.route("/articles", get(route::api::list))
+		.route("/articles/:slug", get(route::api::get))
+		.route("/graphql", post(route::api::graphql))
+		.layer(
+			CorsLayer::new()
+				.allow_origin(Any)
+				.allow_methods([Method::GET, Method::POST])
+				.allow_headers([CONTENT_TYPE]),
+		)
+		.nest("/admin/v1", admin_api);
+	let assets = Router::new()
 		.nest_service("/assets", ServeDir::new("assets"))
 		.nest_service("/assets/article", ServeDir::new(config.article_assets_path))
+		.layer(from_fn_with_state(ctx.clone(), middleware::cache_control));
+	let router = Router::new()
+		.merge(assets)
 		// deprecated route
 		.route(
 			"/avatar/llenotre",
@@ -89,21 +385,66 @@ async fn main() -> io::Result<()> {
 				Redirect::permanent(&url)
 			}),
 		)
+		.route("/article/:id/:title", get(route::article::legacy))
+		.nest("/api", api)
 		.route("/health", get(route::health))
+		.route("/health/live", get(route::health::live))
+		.route("/health/ready", get(route::health::ready))
 		.route("/", get(route::root))
 		.route("/a/:slug", get(route::article::get))
+		.route("/author/:slug", get(route::author))
+		.route("/og/:file", get(route::article::og_image))
+		.route("/img/*path", get(route::image))
 		.route("/bio", get(route::bio))
 		.route("/legal", get(route::legal))
 		.route("/robots.txt", get(gateway_api::robots))
 		.route("/sitemap.xml", get(route::sitemap))
 		.route("/rss", get(route::rss))
-		.fallback(handle_404);
+		.route("/out", get(route::outbound))
+		.route("/webmention", post(route::webmention))
+		.route("/react/:slug", post(route::react))
+		.route("/search", get(route::search))
+		.route("/theme", get(route::theme::set))
+		.route(
+			"/admin/login",
+			get(route::admin::login_page).post(route::admin::login),
+		)
+		.route("/admin/logout", get(route::admin::logout))
+		.route(
+			"/admin/preview",
+			post(route::admin::preview).layer(DefaultBodyLimit::max(config.upload_max_size)),
+		)
+		.route("/admin/editor", get(route::admin::editor_list))
+		.route(
+			"/admin/editor/:name",
+			get(route::admin::editor)
+				.post(route::admin::save_article)
+				.layer(DefaultBodyLimit::max(config.upload_max_size)),
+		)
+		.fallback(handle_404)
+		.layer(DefaultBodyLimit::max(config.body_max_size));
 	#[cfg(feature = "analytics")]
 	let router = router.layer(gateway_api::analytics::AnalyticsLayer::default());
 	let router = router
+		.layer(from_fn_with_state(ctx.clone(), middleware::feed_links))
+		.layer(from_fn_with_state(ctx.clone(), middleware::timeout))
+		.layer(CatchPanicLayer::custom(handle_panic))
+		.layer(from_fn_with_state(ctx.clone(), middleware::report_errors))
+		.layer(from_fn_with_state(ctx.clone(), middleware::rate_limit))
+		.layer(from_fn_with_state(ctx.clone(), middleware::live))
+		.layer(from_fn_with_state(ctx.clone(), middleware::not_found))
+		.layer(from_fn_with_state(ctx.clone(), middleware::error_pages))
 		.layer(LogLayer)
+		.layer(from_fn(middleware::request_id))
 		.with_state(ctx.clone())
 		.into_make_service_with_connect_info::<SocketAddr>();
+	#[cfg(feature = "tls")]
+	if let (Some(cert), Some(key)) = (config.tls_cert_path, config.tls_key_path) {
+		info!("enable TLS");
+		let tls = axum_server::tls_rustls::RustlsConfig::from_pem_file(cert, key).await?;
+		let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+		return axum_server::bind_rustls(addr, tls).serve(router).await;
+	}
 	let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
 	axum::serve(listener, router).await
 }
@@ -0,0 +1,363 @@
+//! HTTP middlewares.
+
+use crate::{
+	route::{admin::Admin, theme::Theme},
+	service::{
+		feed::{self, FeedLinkHtml},
+		report::ErrorReport,
+	},
+	template::{ErrorTemplate, NotFoundTemplate, ServerErrorTemplate, TooManyRequestsTemplate},
+	util::client_ip,
+	Context,
+};
+use askama::Template;
+use axum::{
+	body::Body,
+	extract::{ConnectInfo, MatchedPath, Request, State},
+	http::{
+		header::{
+			ACCEPT, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, REFERER, RETRY_AFTER, USER_AGENT,
+		},
+		HeaderName, HeaderValue, Method, StatusCode,
+	},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use lol_html::{element, html_content::ContentType, HtmlRewriter};
+use std::{
+	hash::{BuildHasher, RandomState},
+	net::SocketAddr,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, OnceLock,
+	},
+};
+use tracing::{info_span, Instrument};
+
+/// The header holding the identifier of a request.
+const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+/// The maximum length of a request identifier provided by a client.
+const REQUEST_ID_MAX_LEN: usize = 64;
+
+/// The identifier of a request, used to correlate logs and error reports.
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+	/// Generates a new random identifier.
+	fn generate() -> Self {
+		static COUNTER: AtomicU64 = AtomicU64::new(0);
+		static HASHER: OnceLock<RandomState> = OnceLock::new();
+		let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+		let id = HASHER.get_or_init(RandomState::new).hash_one(n);
+		Self(format!("{id:016x}"))
+	}
+}
+
+/// Paths of the routes receiving file uploads.
+const UPLOAD_PATHS: &[&str] = &["/api/admin/v1/files"];
+/// Paths which are subject to rate limiting.
+const RATE_LIMITED_PREFIXES: &[&str] = &[
+	"/admin/login",
+	"/api",
+	"/img",
+	"/og",
+	"/out",
+	"/react",
+	"/rss",
+	"/search",
+	"/webmention",
+];
+/// Paths of routes returning JSON, for which no error page is rendered.
+const API_PREFIXES: &[&str] = &["/api"];
+/// Paths whose server errors are expected and not reported, such as failing health checks.
+const UNREPORTED_PREFIXES: &[&str] = &["/health"];
+/// Paths which are not counted as page views.
+const IGNORED_PREFIXES: &[&str] = &[
+	"/admin",
+	"/api",
+	"/assets",
+	"/health",
+	"/img",
+	"/og",
+	"/react",
+	"/webmention",
+];
+
+/// Extensions of assets that rarely change, such as images, fonts and videos.
+const MEDIA_EXTENSIONS: &[&str] = &[
+	"avif", "gif", "ico", "jpeg", "jpg", "mp4", "otf", "png", "svg", "ttf", "webm", "webp",
+	"woff", "woff2",
+];
+
+/// Assigns an identifier to the request, attaches it to the tracing span and returns it in a
+/// response header.
+///
+/// If the request already has an identifier (for example, set by the gateway), it is kept.
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+	let id = req
+		.headers()
+		.get(&X_REQUEST_ID)
+		.and_then(|h| h.to_str().ok())
+		.filter(|id| {
+			!id.is_empty()
+				&& id.len() <= REQUEST_ID_MAX_LEN
+				&& id.chars().all(|c| c.is_ascii_graphic())
+		})
+		.map(|id| RequestId(id.to_owned()))
+		.unwrap_or_else(RequestId::generate);
+	req.extensions_mut().insert(id.clone());
+	let span = info_span!("request", id = id.0);
+	let mut response = next.run(req).instrument(span).await;
+	if let Ok(value) = HeaderValue::from_str(&id.0) {
+		response.headers_mut().insert(X_REQUEST_ID, value);
+	}
+	response
+}
+
+/// Aborts the handling of requests taking too long, with a longer delay for file uploads.
+pub async fn timeout(State(ctx): State<Arc<Context>>, req: Request, next: Next) -> Response {
+	let upload = req.method() == Method::POST && UPLOAD_PATHS.contains(&req.uri().path());
+	let duration = if upload {
+		ctx.upload_timeout
+	} else {
+		ctx.request_timeout
+	};
+	match tokio::time::timeout(duration, next.run(req)).await {
+		Ok(response) => response,
+		Err(_) => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+	}
+}
+
+/// Records the visitor issuing the request, for the live visitors counter.
+pub async fn live(
+	State(ctx): State<Arc<Context>>,
+	ConnectInfo(addr): ConnectInfo<SocketAddr>,
+	req: Request,
+	next: Next,
+) -> Response {
+	let path = req.uri().path();
+	if !IGNORED_PREFIXES.iter().any(|p| path.starts_with(p)) {
+		let user_agent = req
+			.headers()
+			.get(USER_AGENT)
+			.and_then(|h| h.to_str().ok())
+			.unwrap_or_default();
+		ctx.live_visitors.record(
+			client_ip(&ctx.trusted_proxies, req.headers(), addr),
+			user_agent,
+		);
+	}
+	next.run(req).await
+}
+
+/// Records requests resulting in a `404 Not Found` status, to discover broken inbound links.
+pub async fn not_found(State(ctx): State<Arc<Context>>, req: Request, next: Next) -> Response {
+	let uri = req.uri().to_string();
+	let referrer = req
+		.headers()
+		.get(REFERER)
+		.and_then(|h| h.to_str().ok())
+		.map(str::to_owned);
+	let response = next.run(req).await;
+	if response.status() == StatusCode::NOT_FOUND && !uri.starts_with("/admin") {
+		ctx.not_found.record(&uri, referrer.as_deref());
+	}
+	response
+}
+
+/// Rejects requests to expensive or abuse-prone routes from clients exceeding their rate.
+pub async fn rate_limit(
+	State(ctx): State<Arc<Context>>,
+	ConnectInfo(addr): ConnectInfo<SocketAddr>,
+	req: Request,
+	next: Next,
+) -> Response {
+	let path = req.uri().path();
+	if RATE_LIMITED_PREFIXES.iter().any(|p| path.starts_with(p)) {
+		let ip = client_ip(&ctx.trusted_proxies, req.headers(), addr);
+		if let Err(retry_after) = ctx.rate_limiter.check(ip) {
+			let retry_after = retry_after.as_secs_f64().ceil() as u64;
+			return (
+				StatusCode::TOO_MANY_REQUESTS,
+				[(RETRY_AFTER, retry_after.to_string())],
+			)
+				.into_response();
+		}
+	}
+	next.run(req).await
+}
+
+/// The message of a panic that occurred while handling a request, attached to the response.
+#[derive(Clone)]
+pub struct PanicMessage(pub String);
+
+/// Reports responses with a server error status, including panics, to the error webhook.
+pub async fn report_errors(State(ctx): State<Arc<Context>>, req: Request, next: Next) -> Response {
+	let method = req.method().to_string();
+	let route = req
+		.extensions()
+		.get::<MatchedPath>()
+		.map(|p| p.as_str().to_owned())
+		.unwrap_or_else(|| req.uri().path().to_owned());
+	let request_id = req.extensions().get::<RequestId>().map(|id| id.0.clone());
+	let admin = Admin::is_authenticated(&ctx, req.headers());
+	let reported = !UNREPORTED_PREFIXES
+		.iter()
+		.any(|p| req.uri().path().starts_with(p));
+	let response = next.run(req).await;
+	if reported && response.status().is_server_error() {
+		let panic = response
+			.extensions()
+			.get::<PanicMessage>()
+			.map(|m| m.0.clone());
+		ctx.error_reporter.report(ErrorReport {
+			date: Utc::now(),
+			status: response.status().as_u16(),
+			method,
+			route,
+			request_id,
+			admin,
+			panic,
+		});
+	}
+	response
+}
+
+/// Renders an error page for error responses without body, with details depending on the status.
+pub async fn error_pages(State(ctx): State<Arc<Context>>, req: Request, next: Next) -> Response {
+	let path = req.uri().path().to_owned();
+	let accepts_html = req
+		.headers()
+		.get(ACCEPT)
+		.and_then(|h| h.to_str().ok())
+		.is_none_or(|a| a.contains("text/html") || a.contains("*/*"));
+	let render = accepts_html && !API_PREFIXES.iter().any(|p| path.starts_with(p));
+	let headers = req.headers().clone();
+	let request_id = req
+		.extensions()
+		.get::<RequestId>()
+		.map(|id| id.0.clone())
+		.unwrap_or_default();
+	let response = next.run(req).await;
+	let status = response.status();
+	let is_error = status.is_client_error() || status.is_server_error();
+	if !render || !is_error || response.headers().contains_key(CONTENT_TYPE) {
+		return response;
+	}
+	let t = ctx.translations.negotiate(&headers);
+	let theme = Theme::from_headers(&headers);
+	let reference = &request_id;
+	let html = match status {
+		StatusCode::NOT_FOUND => NotFoundTemplate {
+			theme,
+			t,
+			status,
+			reference,
+			suggestions: ctx.suggest_articles(&path),
+		}
+		.render(),
+		StatusCode::TOO_MANY_REQUESTS => TooManyRequestsTemplate {
+			theme,
+			t,
+			status,
+			reference,
+			retry_after: response
+				.headers()
+				.get(RETRY_AFTER)
+				.and_then(|h| h.to_str().ok())
+				.and_then(|s| s.parse().ok()),
+		}
+		.render(),
+		s if s.is_server_error() => ServerErrorTemplate {
+			theme,
+			t,
+			status,
+			reference,
+		}
+		.render(),
+		_ => ErrorTemplate {
+			theme,
+			t,
+			status,
+			reference,
+		}
+		.render(),
+	};
+	let Ok(html) = html else {
+		return response;
+	};
+	let (mut parts, _) = response.into_parts();
+	parts.headers.remove(CONTENT_LENGTH);
+	parts.headers.insert(
+		CONTENT_TYPE,
+		HeaderValue::from_static("text/html; charset=utf-8"),
+	);
+	Response::from_parts(parts, Body::from(html))
+}
+
+/// Advertises the feeds relevant to the requested page by injecting `<link rel="alternate">`
+/// tags into the head of HTML pages, so that feed readers can discover them.
+pub async fn feed_links(State(ctx): State<Arc<Context>>, req: Request, next: Next) -> Response {
+	if !matches!(*req.method(), Method::GET | Method::HEAD) {
+		return next.run(req).await;
+	}
+	let links: String = feed::get_feeds(req.uri())
+		.iter()
+		.map(|f| FeedLinkHtml(f, &ctx.base_url).to_string())
+		.collect();
+	let response = next.run(req).await;
+	let html = response
+		.headers()
+		.get(CONTENT_TYPE)
+		.and_then(|h| h.to_str().ok())
+		.is_some_and(|t| t.starts_with("text/html"));
+	if response.status() != StatusCode::OK || !html {
+		return response;
+	}
+	let (mut parts, body) = response.into_parts();
+	let Ok(body) = axum::body::to_bytes(body, usize::MAX).await else {
+		return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+	};
+	let mut output = Vec::with_capacity(body.len() + links.len());
+	let mut rewriter = HtmlRewriter::new(
+		lol_html::Settings {
+			element_content_handlers: vec![element!("head", |el| {
+				el.append(&links, ContentType::Html);
+				Ok(())
+			})],
+			..lol_html::Settings::default()
+		},
+		|c: &[u8]| output.extend_from_slice(c),
+	);
+	let res = rewriter.write(&body).and_then(|_| rewriter.end());
+	let body = match res {
+		Ok(()) => Body::from(output),
+		Err(_) => Body::from(body),
+	};
+	parts.headers.remove(CONTENT_LENGTH);
+	Response::from_parts(parts, body)
+}
+
+/// Sets the `Cache-Control` header on assets, with a longer duration for media than for other
+/// files.
+pub async fn cache_control(State(ctx): State<Arc<Context>>, req: Request, next: Next) -> Response {
+	let media = req
+		.uri()
+		.path()
+		.rsplit_once('.')
+		.is_some_and(|(_, ext)| MEDIA_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+	let mut response = next.run(req).await;
+	let status = response.status();
+	if status.is_success() || status == StatusCode::NOT_MODIFIED {
+		let max_age = if media {
+			ctx.media_max_age
+		} else {
+			ctx.asset_max_age
+		};
+		let value = HeaderValue::from_str(&format!("public, max-age={max_age}")).unwrap();
+		response.headers_mut().insert(CACHE_CONTROL, value);
+	}
+	response
+}
@@ -0,0 +1,552 @@
+//! Administration routes.
+
+use crate::{
+	route::{article, theme::Theme},
+	service::{
+		article::{Article, ArticleJson},
+		editor,
+		feed::FeedEstimate,
+		files::{self, FileEntry},
+		not_found::NotFoundEntry,
+		outbound::OutboundEntry,
+		reaction::Reaction,
+		webhook::Delivery,
+		webmention::Mention,
+	},
+	template::{AdminEditorTemplate, AdminLoginTemplate},
+	util::{get_cookie, secret_eq},
+	Context,
+};
+use axum::{
+	async_trait,
+	extract::{FromRequestParts, Multipart, Path, Query, State},
+	http::{
+		header::{AUTHORIZATION, CACHE_CONTROL, SET_COOKIE},
+		request::Parts,
+		HeaderMap, StatusCode,
+	},
+	response::{Html, IntoResponse, Redirect, Response},
+	Form, Json,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{io, path::PathBuf, sync::Arc};
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::{error, info};
+use utoipa::{
+	openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+	Modify, OpenApi, ToSchema,
+};
+
+/// The slug given to previewed articles that do not specify one.
+const PREVIEW_SLUG: &str = "preview";
+/// The name of the cookie holding the administrator's session.
+const SESSION_COOKIE: &str = "admin_session";
+
+/// Returns the value of the session cookie for the given administrator token.
+///
+/// The token itself is not stored in the cookie so that it does not leak with it.
+fn session_value(token: &str) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes()).unwrap();
+	mac.update(b"admin-session");
+	hex::encode(mac.finalize().into_bytes())
+}
+
+/// Extractor which succeeds only if the request is authenticated as the administrator.
+///
+/// The administrator is authenticated either with the session cookie set by [`login`], or with
+/// the administrator token or one of the API tokens in the `Authorization: Bearer` header.
+///
+/// The gateway does not expose sessions to the blog, hence the local token.
+pub struct Admin;
+
+impl Admin {
+	/// Tells whether the request with the given `headers` is authenticated as the administrator.
+	pub fn is_authenticated(ctx: &Context, headers: &HeaderMap) -> bool {
+		let admin_token = ctx.admin_token.as_deref();
+		let bearer = headers
+			.get(AUTHORIZATION)
+			.and_then(|h| h.to_str().ok())
+			.and_then(|h| h.strip_prefix("Bearer "))
+			.map(str::trim);
+		if let Some(token) = bearer {
+			return admin_token.is_some_and(|t| secret_eq(token, t))
+				|| ctx.admin_api_tokens.iter().any(|t| secret_eq(token, t));
+		}
+		let session = get_cookie(headers, SESSION_COOKIE);
+		session
+			.zip(admin_token)
+			.is_some_and(|(session, token)| secret_eq(session, &session_value(token)))
+	}
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<Context>> for Admin {
+	type Rejection = StatusCode;
+
+	async fn from_request_parts(
+		parts: &mut Parts,
+		ctx: &Arc<Context>,
+	) -> Result<Self, Self::Rejection> {
+		if Self::is_authenticated(ctx, &parts.headers) {
+			Ok(Self)
+		} else {
+			Err(StatusCode::NOT_FOUND)
+		}
+	}
+}
+
+pub async fn login_page(
+	State(ctx): State<Arc<Context>>,
+	theme: Theme,
+	headers: HeaderMap,
+) -> Response {
+	if ctx.admin_token.is_none() {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	AdminLoginTemplate {
+		t: ctx.translations.negotiate(&headers),
+		theme,
+	}
+	.into_response()
+}
+
+/// The payload of the login form.
+#[derive(Deserialize)]
+pub struct LoginPayload {
+	/// The administrator token.
+	token: String,
+}
+
+pub async fn login(
+	State(ctx): State<Arc<Context>>,
+	Form(payload): Form<LoginPayload>,
+) -> Response {
+	let Some(expected) = &ctx.admin_token else {
+		return StatusCode::NOT_FOUND.into_response();
+	};
+	if !secret_eq(&payload.token, expected) {
+		return StatusCode::UNAUTHORIZED.into_response();
+	}
+	let cookie = format!(
+		"{SESSION_COOKIE}={session}; Path=/; HttpOnly; Secure; SameSite=Strict",
+		session = session_value(expected)
+	);
+	([(SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
+
+/// The payload of the preview route.
+#[derive(Deserialize)]
+pub struct PreviewPayload {
+	/// The manifest of the article, in TOML. If not set, placeholder metadata is used.
+	#[serde(default)]
+	manifest: Option<String>,
+	/// The content of the article, in Markdown.
+	content: String,
+}
+
+/// Compiles and renders an article that is not stored, as it would be published, so that it can
+/// be previewed.
+pub async fn preview(
+	State(ctx): State<Arc<Context>>,
+	_: Admin,
+	theme: Theme,
+	headers: HeaderMap,
+	Form(payload): Form<PreviewPayload>,
+) -> Response {
+	let manifest = payload
+		.manifest
+		.filter(|m| !m.trim().is_empty())
+		.unwrap_or_else(|| {
+			let date = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+			format!("title = \"Preview\"\npost_date = \"{date}\"\ndescription = \"\"\n")
+		});
+	let license = ctx.default_license.as_deref();
+	let article = match Article::parse(&manifest, PREVIEW_SLUG, payload.content, license) {
+		Ok(article) => article,
+		Err(error) => return (StatusCode::BAD_REQUEST, error.to_string()).into_response(),
+	};
+	let (content, _) = article.compile(&ctx.outbound);
+	let t = ctx.translations.negotiate(&headers);
+	let reactions = Reaction::ALL.into_iter().map(|r| (r, 0)).collect();
+	match article::render(&ctx, t, theme, &article, &content, vec![], reactions) {
+		Ok(html) => ([(CACHE_CONTROL, "no-store")], Html(html)).into_response(),
+		Err(error) => {
+			error!(%error, "could not render preview");
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
+
+/// Renders the editor page, with the article directory `name` being edited if set.
+async fn render_editor(
+	ctx: &Context,
+	theme: Theme,
+	headers: &HeaderMap,
+	name: Option<&str>,
+) -> Response {
+	let res = async {
+		let articles = editor::list(&ctx.articles_path).await?;
+		let (manifest, content) = match name {
+			Some(name) => editor::read(&ctx.articles_path, name).await?,
+			None => Default::default(),
+		};
+		io::Result::Ok((articles, manifest, content))
+	};
+	match res.await {
+		Ok((articles, manifest, content)) => AdminEditorTemplate {
+			t: ctx.translations.negotiate(headers),
+			theme,
+			articles,
+			name,
+			manifest,
+			content,
+		}
+		.into_response(),
+		Err(error) => {
+			error!(%error, "could not read articles");
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
+
+/// The query of the editor page.
+#[derive(Deserialize)]
+pub struct EditorQuery {
+	/// The name of an article directory to create.
+	slug: Option<String>,
+}
+
+/// Lists article directories, or redirects to the editor of the one given in the query.
+pub async fn editor_list(
+	State(ctx): State<Arc<Context>>,
+	_: Admin,
+	theme: Theme,
+	headers: HeaderMap,
+	Query(query): Query<EditorQuery>,
+) -> Response {
+	let Some(slug) = query.slug.filter(|s| !s.trim().is_empty()) else {
+		return render_editor(&ctx, theme, &headers, None).await;
+	};
+	match files::check_name(slug.trim()) {
+		Some(name) => Redirect::to(&format!("/admin/editor/{name}")).into_response(),
+		None => (StatusCode::BAD_REQUEST, "invalid slug").into_response(),
+	}
+}
+
+/// Edits the article in the directory `name`, which is created on save if it does not exist.
+pub async fn editor(
+	State(ctx): State<Arc<Context>>,
+	_: Admin,
+	theme: Theme,
+	headers: HeaderMap,
+	Path(name): Path<String>,
+) -> Response {
+	if files::check_name(&name).is_none() {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	render_editor(&ctx, theme, &headers, Some(&name)).await
+}
+
+/// The payload of the editor's form.
+#[derive(Deserialize)]
+pub struct EditorPayload {
+	/// The manifest of the article, in TOML.
+	manifest: String,
+	/// The content of the article, in Markdown.
+	content: String,
+	/// The message of the commit. If empty, a default message is used.
+	#[serde(default)]
+	message: String,
+}
+
+/// Writes the article in the directory `name` and commits it to the articles repository.
+///
+/// Changes are visible on the blog once articles are compiled again, at the next start.
+pub async fn save_article(
+	State(ctx): State<Arc<Context>>,
+	_: Admin,
+	Path(name): Path<String>,
+	Form(payload): Form<EditorPayload>,
+) -> Response {
+	if files::check_name(&name).is_none() {
+		return (StatusCode::BAD_REQUEST, "invalid slug").into_response();
+	}
+	// Browsers submit text areas with CRLF line endings
+	let manifest = payload.manifest.replace("\r\n", "\n");
+	let content = payload.content.replace("\r\n", "\n");
+	if let Err(error) = Article::parse(&manifest, &name, String::new(), None) {
+		return (StatusCode::BAD_REQUEST, error.to_string()).into_response();
+	}
+	let message = match payload.message.trim() {
+		"" => format!("Update {name}"),
+		message => message.to_owned(),
+	};
+	match editor::save(&ctx.articles_path, &name, &manifest, &content, &message).await {
+		Ok(committed) => {
+			info!(name, committed, "article saved");
+			Redirect::to(&format!("/admin/editor/{name}")).into_response()
+		}
+		Err(error) => {
+			error!(%error, name, "could not save article");
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
+
+pub async fn logout() -> Response {
+	let cookie =
+		format!("{SESSION_COOKIE}=; Path=/; HttpOnly; Secure; SameSite=Strict; Max-Age=0");
+	([(SET_COOKIE, cookie)], Redirect::to("/")).into_response()
+}
+
+/// The documentation of the administration API.
+#[derive(OpenApi)]
+#[openapi(
+	info(title = "Blog administration API"),
+	servers((url = "/api/admin/v1")),
+	paths(
+		drafts,
+		live,
+		not_found,
+		feed_subscribers,
+		outbound,
+		webhooks,
+		webmentions,
+		list_files,
+		upload_file,
+		delete_file
+	),
+	components(schemas(
+		ArticleJson,
+		LiveStats,
+		NotFoundEntry,
+		FeedEstimate,
+		OutboundEntry,
+		Delivery,
+		Mention,
+		FileEntry,
+		UploadedFile
+	)),
+	modifiers(&BearerAuth),
+	security(("token" = []))
+)]
+pub struct ApiDoc;
+
+/// Declares the bearer token authentication of the administration API.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+	fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+		let components = openapi.components.get_or_insert_with(Default::default);
+		components.add_security_scheme(
+			"token",
+			SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+		);
+	}
+}
+
+pub async fn openapi(_: Admin) -> Response {
+	Json(ApiDoc::openapi()).into_response()
+}
+
+/// Lists articles that are not public yet.
+#[utoipa::path(get, path = "/drafts", responses(
+	(status = 200, description = "Drafts, ordered by post date", body = [ArticleJson])
+))]
+pub async fn drafts(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	let drafts: Vec<_> = ctx
+		.articles
+		.iter()
+		.filter(|(a, _)| !a.is_public())
+		.map(|(a, _)| ArticleJson::new(a, &ctx.base_url, None))
+		.collect();
+	Json(drafts).into_response()
+}
+
+/// Statistics about the visitors currently reading the blog.
+#[derive(Serialize, ToSchema)]
+pub struct LiveStats {
+	/// The number of visitors.
+	visitors: usize,
+	/// The duration, in seconds, after which an inactive visitor is not counted anymore.
+	window_secs: u64,
+}
+
+/// Returns the number of visitors currently reading the blog.
+#[utoipa::path(get, path = "/live", responses(
+	(status = 200, body = LiveStats)
+))]
+pub async fn live(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	Json(LiveStats {
+		visitors: ctx.live_visitors.count(),
+		window_secs: ctx.live_visitors.window().as_secs(),
+	})
+	.into_response()
+}
+
+/// Returns the URIs that have been requested but do not exist.
+#[utoipa::path(get, path = "/not-found", responses(
+	(status = 200, body = [NotFoundEntry])
+))]
+pub async fn not_found(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	Json(ctx.not_found.report()).into_response()
+}
+
+/// Returns the estimated number of subscribers to the RSS feed.
+#[utoipa::path(get, path = "/feed-subscribers", responses(
+	(status = 200, body = FeedEstimate)
+))]
+pub async fn feed_subscribers(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	Json(ctx.feed_subscribers.estimate()).into_response()
+}
+
+/// Returns the number of clicks on external links.
+#[utoipa::path(get, path = "/outbound", responses(
+	(status = 200, body = [OutboundEntry])
+))]
+pub async fn outbound(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	Json(ctx.outbound.report()).into_response()
+}
+
+/// Returns the latest deliveries of events to webhooks, from the newest to the oldest.
+#[utoipa::path(get, path = "/webhooks", responses(
+	(status = 200, body = [Delivery])
+))]
+pub async fn webhooks(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	Json(ctx.webhooks.report()).into_response()
+}
+
+/// Returns the verified webmentions, by article slug.
+#[utoipa::path(get, path = "/webmentions", responses(
+	(status = 200, body = std::collections::HashMap<String, Vec<Mention>>)
+))]
+pub async fn webmentions(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	Json(ctx.webmentions.report()).into_response()
+}
+
+/// Lists the files of the article assets directory.
+#[utoipa::path(get, path = "/files", responses(
+	(status = 200, body = [FileEntry])
+))]
+pub async fn list_files(State(ctx): State<Arc<Context>>, _: Admin) -> Response {
+	match files::list(&ctx.assets_path) {
+		Ok(files) => Json(files).into_response(),
+		Err(error) => {
+			error!(%error, "could not list files");
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
+
+/// A file that has just been uploaded.
+#[derive(Serialize, ToSchema)]
+pub struct UploadedFile {
+	/// The path of the file, relative to the assets directory.
+	path: String,
+	/// The URL at which the file is served.
+	url: String,
+	/// The size of the file, in bytes.
+	size: usize,
+}
+
+/// Stores the file of the `file` field of the multipart request in the assets directory.
+///
+/// An existing file is never overwritten.
+#[utoipa::path(post, path = "/files", request_body(
+	content = String,
+	content_type = "multipart/form-data",
+	description = "The file, in the `file` field"
+), responses(
+	(status = 201, body = UploadedFile),
+	(status = 400, description = "Missing file or invalid file name"),
+	(status = 409, description = "A file with the same name already exists"),
+	(status = 413, description = "The file is too large"),
+	(status = 415, description = "The file type is not allowed")
+))]
+pub async fn upload_file(
+	State(ctx): State<Arc<Context>>,
+	_: Admin,
+	mut multipart: Multipart,
+) -> Response {
+	loop {
+		let field = match multipart.next_field().await {
+			Ok(Some(field)) => field,
+			Ok(None) => return (StatusCode::BAD_REQUEST, "missing `file` field").into_response(),
+			Err(error) => return error.into_response(),
+		};
+		if field.name() != Some("file") {
+			continue;
+		}
+		let Some(name) = field.file_name().and_then(files::check_name) else {
+			return (StatusCode::BAD_REQUEST, "invalid file name").into_response();
+		};
+		let name = name.to_owned();
+		let mime = field.content_type().unwrap_or_default();
+		if !files::is_allowed_type(&name, mime) {
+			return (StatusCode::UNSUPPORTED_MEDIA_TYPE, "file type not allowed").into_response();
+		}
+		let data = match field.bytes().await {
+			Ok(data) => data,
+			Err(error) => return error.into_response(),
+		};
+		let path = ctx.assets_path.join(&name);
+		let file = fs::OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(&path)
+			.await;
+		let res = match file {
+			Ok(mut file) => file.write_all(&data).await,
+			Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+				return (StatusCode::CONFLICT, "file already exists").into_response();
+			}
+			Err(error) => Err(error),
+		};
+		if let Err(error) = res {
+			error!(%error, name, "could not store file");
+			let _ = fs::remove_file(&path).await;
+			return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+		}
+		info!(name, size = data.len(), "file uploaded");
+		let body = UploadedFile {
+			url: files::get_url(name.as_ref()),
+			path: name,
+			size: data.len(),
+		};
+		return (StatusCode::CREATED, Json(body)).into_response();
+	}
+}
+
+/// Deletes a file of the article assets directory.
+#[utoipa::path(delete, path = "/files/{path}", params(
+	("path" = String, Path, description = "The path of the file, relative to the assets directory")
+), responses(
+	(status = 204, description = "The file has been deleted"),
+	(status = 400, description = "Invalid path"),
+	(status = 404, description = "The file does not exist")
+))]
+pub async fn delete_file(
+	State(ctx): State<Arc<Context>>,
+	_: Admin,
+	Path(path): Path<PathBuf>,
+) -> Response {
+	if !files::is_valid_path(&path) {
+		return StatusCode::BAD_REQUEST.into_response();
+	}
+	match fs::remove_file(ctx.assets_path.join(&path)).await {
+		Ok(()) => {
+			info!(path = %path.display(), "file deleted");
+			StatusCode::NO_CONTENT.into_response()
+		}
+		Err(error) if error.kind() == io::ErrorKind::NotFound => {
+			StatusCode::NOT_FOUND.into_response()
+		}
+		Err(error) => {
+			error!(%error, "could not delete file");
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
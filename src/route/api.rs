@@ -0,0 +1,110 @@
+//! JSON API routes.
+
+use crate::{
+	route::admin::Admin,
+	service::article::{ArticleJson, ArticleQuery},
+	Context,
+};
+use axum::{
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The default number of articles per page.
+const DEFAULT_PER_PAGE: usize = 20;
+/// The maximum number of articles per page.
+const MAX_PER_PAGE: usize = 100;
+
+/// Pagination parameters.
+#[derive(Deserialize)]
+pub struct Pagination {
+	/// The page number, starting at `1`.
+	#[serde(default = "default_page")]
+	page: usize,
+	/// The number of articles per page.
+	#[serde(default = "default_per_page")]
+	per_page: usize,
+}
+
+fn default_page() -> usize {
+	1
+}
+
+fn default_per_page() -> usize {
+	DEFAULT_PER_PAGE
+}
+
+/// A page of articles.
+#[derive(Serialize)]
+struct ArticlePage<'a> {
+	/// The current page number.
+	page: usize,
+	/// The number of articles per page.
+	per_page: usize,
+	/// The total number of articles matching the query.
+	total: usize,
+	/// The articles on the page.
+	articles: Vec<ArticleJson<'a>>,
+}
+
+pub async fn list(
+	State(ctx): State<Arc<Context>>,
+	admin: Option<Admin>,
+	Query(mut query): Query<ArticleQuery>,
+	Query(pagination): Query<Pagination>,
+) -> Response {
+	query.private = admin.is_some();
+	let page = pagination.page.max(1);
+	let per_page = pagination.per_page.clamp(1, MAX_PER_PAGE);
+	let total = ctx.query_articles(&query).count();
+	let articles = ctx
+		.query_articles(&query)
+		.skip((page - 1).saturating_mul(per_page))
+		.take(per_page)
+		.map(|a| ArticleJson::new(a, &ctx.base_url, None))
+		.collect();
+	Json(ArticlePage {
+		page,
+		per_page,
+		total,
+		articles,
+	})
+	.into_response()
+}
+
+/// Query parameters of the article route.
+#[derive(Deserialize)]
+pub struct GetParams {
+	/// If `true`, the compiled content of the article is included.
+	#[serde(default)]
+	content: bool,
+}
+
+pub async fn get(
+	State(ctx): State<Arc<Context>>,
+	Path(slug): Path<String>,
+	admin: Option<Admin>,
+	Query(params): Query<GetParams>,
+) -> Response {
+	let Some((article, content)) = ctx.get_article(&slug) else {
+		return StatusCode::NOT_FOUND.into_response();
+	};
+	if admin.is_none() && !article.is_public() {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	let content = params.content.then_some(content.as_str());
+	Json(ArticleJson::new(article, &ctx.base_url, content)).into_response()
+}
+
+/// Executes a query on the GraphQL API.
+pub async fn graphql(
+	State(ctx): State<Arc<Context>>,
+	Json(req): Json<async_graphql::Request>,
+) -> Response {
+	let req = req.data(ctx.clone());
+	Json(ctx.graphql_schema.execute(req).await).into_response()
+}
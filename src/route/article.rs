@@ -1,33 +1,163 @@
-use crate::Context;
+use crate::{
+	route::{admin::Admin, theme::Theme},
+	service::{
+		article::{Article, ArticleAuthorsHtml},
+		i18n::Locale,
+		reaction::Reaction,
+		webmention::Mention,
+	},
+	template::ArticleTemplate,
+	util::{http_date, is_fresh},
+	Context,
+};
+use askama::Template;
 use axum::{
 	body::Body,
 	extract::{Path, State},
-	http::StatusCode,
-	response::{Html, IntoResponse, Response},
+	http::{
+		header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, LAST_MODIFIED, VARY},
+		HeaderMap, StatusCode,
+	},
+	response::{Html, IntoResponse, Redirect, Response},
 };
+use html_escape::encode_text;
 use std::sync::Arc;
 
-pub async fn get(State(ctx): State<Arc<Context>>, Path(slug): Path<String>) -> Response {
+pub async fn get(
+	State(ctx): State<Arc<Context>>,
+	Path(slug): Path<String>,
+	admin: Option<Admin>,
+	theme: Theme,
+	headers: HeaderMap,
+) -> Response {
+	if let Some(slug) = slug.strip_suffix(".md") {
+		return get_markdown(&ctx, slug);
+	}
 	let Some((article, content)) = ctx.get_article(&slug) else {
 		return (StatusCode::NOT_FOUND, Body::empty()).into_response();
 	};
-	if !article.is_public() {
+	if admin.is_none() && !article.is_public() {
 		return StatusCode::NOT_FOUND.into_response();
 	}
+	let t = ctx.translations.negotiate(&headers);
+	let key = format!("article:{}:{theme}:{slug}", t.lang());
+	let (mentions_revision, mentions) = ctx.webmentions.get(&slug);
+	let (reactions_revision, reactions) = ctx.reactions.get(&slug);
+	// Both revisions only increase, so their sum changes whenever one of them does
+	let revision = mentions_revision + reactions_revision;
+	let page = ctx.page_cache.get_or_render(&key, revision, || {
+		let reactions = Reaction::ALL.into_iter().zip(reactions).collect();
+		render(&ctx, t, theme, article, content, mentions, reactions)
+	});
+	let Ok(page) = page else {
+		return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+	};
+	// The page changes when the article is compiled or published, and with its mentions and
+	// reactions. Changes before the server started are covered by the compilation date
+	let last_modified = [
+		ctx.webmentions.last_modified(&slug),
+		ctx.reactions.last_modified(&slug),
+	]
+	.into_iter()
+	.flatten()
+	.chain([ctx.compile_date, article.post_date])
+	.max()
+	.unwrap_or(ctx.compile_date);
+	// Drafts must not be stored by any cache. Other pages depend on the theme cookie
+	let (cache_control, vary) = if admin.is_some() {
+		("private, no-store", "accept-language")
+	} else {
+		("no-cache", "cookie, accept-language")
+	};
+	let cache_headers = [
+		(ETAG, page.etag.clone()),
+		(LAST_MODIFIED, http_date(&last_modified)),
+		(CACHE_CONTROL, cache_control.to_owned()),
+		(VARY, vary.to_owned()),
+	];
+	if is_fresh(&headers, &page.etag, &last_modified) {
+		return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+	}
+	(cache_headers, Html(page.html)).into_response()
+}
+
+/// Renders the page of the given article, with its compiled `content`, `mentions` and
+/// `reactions`.
+pub fn render(
+	ctx: &Context,
+	t: Locale,
+	theme: Theme,
+	article: &Article,
+	content: &str,
+	mentions: Vec<Mention>,
+	reactions: Vec<(Reaction, u64)>,
+) -> askama::Result<String> {
 	let tags: String = article
 		.tags
 		.iter()
 		.map(|s| s.as_ref())
 		.fold(String::new(), |n1, n2: &str| n1 + "," + n2);
-	let post_date = article.post_date.to_rfc3339();
-	let html = include_str!("../../pages/article.html");
-	let html = html.replace("{article.tags}", &tags);
-	let html = html.replace("{article.url}", &article.get_url());
-	let html = html.replace("{article.title}", &article.title);
-	let html = html.replace("{article.date}", &post_date);
-	let html = html.replace("{article.description}", &article.description);
-	let html = html.replace("{article.cover_url}", &article.cover_url);
-	let html = html.replace("{article.content}", &content);
-	let html = html.replace("{discord}", &ctx.discord_invite);
-	Html(html).into_response()
+	ArticleTemplate {
+		t,
+		theme,
+		article,
+		content,
+		url: article.get_url(&ctx.base_url),
+		tags,
+		date: article.post_date.to_rfc3339(),
+		authors: ArticleAuthorsHtml(article).to_string(),
+		license: article
+			.license
+			.as_deref()
+			.map(|l| t.get_with("license", &[("license", &encode_text(l))])),
+		json_ld: article.get_json_ld(&ctx.base_url),
+		discord: &ctx.discord_invite,
+		webmention_endpoint: ctx
+			.webmentions
+			.is_enabled()
+			.then(|| format!("{}/webmention", ctx.base_url)),
+		mentions,
+		reactions,
+	}
+	.render()
+}
+
+/// Returns the Markdown source of the article with the given slug.
+fn get_markdown(ctx: &Context, slug: &str) -> Response {
+	match ctx.get_article(slug) {
+		Some((article, _)) if article.is_public() => (
+			[(CONTENT_TYPE, "text/markdown; charset=utf-8")],
+			article.markdown.clone(),
+		)
+			.into_response(),
+		_ => StatusCode::NOT_FOUND.into_response(),
+	}
+}
+
+/// Returns the generated preview image of an article.
+pub async fn og_image(State(ctx): State<Arc<Context>>, Path(file): Path<String>) -> Response {
+	let image = file.strip_suffix(".png").and_then(|slug| {
+		let (article, _) = ctx.get_article(slug)?;
+		if !article.is_public() {
+			return None;
+		}
+		ctx.og_images.get(slug)
+	});
+	match image {
+		Some(image) => ([(CONTENT_TYPE, "image/png")], image.clone()).into_response(),
+		None => StatusCode::NOT_FOUND.into_response(),
+	}
+}
+
+/// Redirects from an article's URL on the previous version of the blog.
+pub async fn legacy(
+	State(ctx): State<Arc<Context>>,
+	Path((id, _title)): Path<(String, String)>,
+) -> Response {
+	match ctx.get_legacy_article(&id) {
+		Some(article) if article.is_public() => {
+			Redirect::permanent(&article.get_path()).into_response()
+		}
+		_ => StatusCode::NOT_FOUND.into_response(),
+	}
 }
@@ -0,0 +1,73 @@
+//! Health endpoints, consumed by orchestrators.
+
+use crate::Context;
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde_json::json;
+use std::{
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// The timeout for the gateway reachability check.
+const GATEWAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Liveness: the server is running and able to handle requests.
+pub async fn live() -> Response {
+	Json(json!({
+		"status": "ok",
+	}))
+	.into_response()
+}
+
+/// Readiness: the server is able to serve its content.
+pub async fn ready(State(ctx): State<Arc<Context>>) -> Response {
+	// The server starts only once articles have been compiled successfully
+	let articles = json!({
+		"ok": true,
+		"count": ctx.articles.len(),
+		"compiled_at": ctx.compile_date,
+	});
+	let start = Instant::now();
+	let res = ctx
+		.http_client
+		.get(&ctx.gateway_config.gateway_url)
+		.timeout(GATEWAY_TIMEOUT)
+		.send()
+		.await;
+	let latency_ms = start.elapsed().as_millis() as u64;
+	let gateway = match &res {
+		// Any response means the gateway is reachable
+		Ok(_) => json!({
+			"ok": true,
+			"latency_ms": latency_ms,
+		}),
+		// The error is logged rather than returned, since it may reveal internal addresses
+		Err(error) => {
+			warn!(%error, "gateway is unreachable");
+			json!({
+				"ok": false,
+				"latency_ms": latency_ms,
+			})
+		}
+	};
+	let ready = res.is_ok();
+	let status = if ready {
+		StatusCode::OK
+	} else {
+		StatusCode::SERVICE_UNAVAILABLE
+	};
+	let body = json!({
+		"status": if ready { "ok" } else { "unavailable" },
+		"checks": {
+			"articles": articles,
+			"gateway": gateway,
+		},
+	});
+	(status, Json(body)).into_response()
+}
@@ -1,67 +1,383 @@
 use crate::{
-	service::article::{ArticleListHtml, ArticleRss, ArticleSitemap},
+	route::{admin::Admin, theme::Theme},
+	service::{
+		article::{
+			ArticleListHtml, ArticleQuery, ArticleRss, ArticleSearchHtml, ArticleSitemap,
+			ArticleSort, AuthorHtml,
+		},
+		feed::FeedParams,
+		files, image,
+		reaction::Reaction,
+		webmention,
+	},
+	template::{AuthorTemplate, BioTemplate, IndexTemplate, LegalTemplate, SearchTemplate},
+	util::{client_ip, etag, http_date, is_fresh},
 	Context,
 };
+use askama::Template;
 use axum::{
-	extract::State,
-	http::header::CONTENT_TYPE,
-	response::{Html, IntoResponse, Response},
+	extract::{ConnectInfo, Path, Query, State},
+	http::{
+		header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, LAST_MODIFIED, LOCATION, USER_AGENT, VARY},
+		HeaderMap, StatusCode,
+	},
+	response::{Html, IntoResponse, Redirect, Response},
+	Form,
 };
-use std::sync::Arc;
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use serde::Deserialize;
+use std::{cmp::Reverse, net::SocketAddr, path::PathBuf, sync::Arc};
+use tracing::{error, warn};
 
+pub mod admin;
+pub mod api;
 pub mod article;
+pub mod health;
+pub mod theme;
 
 pub async fn health() -> &'static str {
 	"OK"
 }
 
-pub async fn root(State(ctx): State<Arc<Context>>) -> Response {
-	let articles: String = ctx
-		.list_articles()
-		.filter(|a| a.is_public())
-		.map(|a| ArticleListHtml(a).to_string())
+pub async fn root(
+	State(ctx): State<Arc<Context>>,
+	admin: Option<Admin>,
+	theme: Theme,
+	headers: HeaderMap,
+) -> Response {
+	let query = ArticleQuery {
+		private: admin.is_some(),
+		..Default::default()
+	};
+	let t = ctx.translations.negotiate(&headers);
+	let template = IndexTemplate {
+		t,
+		theme,
+		base_url: &ctx.base_url,
+		discord: &ctx.discord_invite,
+		gateway: &ctx.gateway_config.gateway_url,
+		articles: ctx
+			.query_articles(&query)
+			.map(|a| ArticleListHtml(a, t))
+			.collect(),
+	};
+	// The administrator's view is not cached since it contains private articles
+	if admin.is_some() {
+		let headers = [
+			(CACHE_CONTROL, "private, no-store"),
+			(VARY, "accept-language"),
+		];
+		return (headers, template).into_response();
+	}
+	// Scheduled articles can become public after compilation
+	let version = template.articles.len() as u64;
+	let key = format!("index:{}:{theme}", t.lang());
+	match ctx
+		.page_cache
+		.get_or_render(&key, version, || template.render())
+	{
+		Ok(page) => ([(VARY, "cookie, accept-language")], Html(page.html)).into_response(),
+		Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+	}
+}
+
+pub async fn search(
+	State(ctx): State<Arc<Context>>,
+	admin: Option<Admin>,
+	Query(mut query): Query<ArticleQuery>,
+	theme: Theme,
+	headers: HeaderMap,
+) -> Response {
+	query.private = admin.is_some();
+	let q = query.q.take().unwrap_or_default();
+	let t = ctx.translations.negotiate(&headers);
+	let articles = if q.trim().is_empty() {
+		ctx.query_articles(&query)
+			.map(|a| ArticleSearchHtml(a, String::new(), t))
+			.collect()
+	} else {
+		let hits = match ctx.search_index.search(&q) {
+			Ok(hits) => hits,
+			Err(error) => {
+				error!(%error, "search failed");
+				return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+			}
+		};
+		let mut articles: Vec<_> = hits
+			.into_iter()
+			.filter_map(|hit| {
+				let (article, content) = ctx.get_article(&hit.slug)?;
+				query.matches(article, content).then_some(ArticleSearchHtml(
+					article,
+					hit.snippet,
+					t,
+				))
+			})
+			.collect();
+		match query.sort {
+			ArticleSort::Relevance => {}
+			ArticleSort::Date => articles.sort_by_key(|a| Reverse(a.0.post_date)),
+			ArticleSort::Popular => {
+				articles.sort_by_cached_key(|a| Reverse(ctx.reactions.total(&a.0.slug)))
+			}
+		}
+		articles
+	};
+	let template = SearchTemplate {
+		t,
+		theme,
+		q: &q,
+		tag: query.tag.as_deref().unwrap_or_default(),
+		year: query.year,
+		sort: query.sort,
+		articles,
+	};
+	([(VARY, "accept-language")], template).into_response()
+}
+
+pub async fn author(
+	State(ctx): State<Arc<Context>>,
+	Path(slug): Path<String>,
+	admin: Option<Admin>,
+	theme: Theme,
+	headers: HeaderMap,
+) -> Response {
+	let Some(author) = ctx.get_author(&slug) else {
+		return StatusCode::NOT_FOUND.into_response();
+	};
+	let query = ArticleQuery {
+		author: Some(slug),
+		private: admin.is_some(),
+		..Default::default()
+	};
+	let t = ctx.translations.negotiate(&headers);
+	let articles: Vec<_> = ctx
+		.query_articles(&query)
+		.map(|a| ArticleListHtml(a, t))
 		.collect();
-	let html = include_str!("../../pages/index.html");
-	let html = html.replace("{discord}", &ctx.discord_invite);
-	let html = html.replace("{gateway}", &ctx.gateway_config.gateway_url);
-	let html = html.replace("{articles}", &articles);
-	Html(html).into_response()
+	if articles.is_empty() {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	let template = AuthorTemplate {
+		t,
+		theme,
+		author,
+		author_html: AuthorHtml(author).to_string(),
+		articles,
+	};
+	([(VARY, "accept-language")], template).into_response()
+}
+
+/// Query parameters of the outbound link tracker.
+#[derive(Deserialize)]
+pub struct OutboundParams {
+	/// The URL to redirect to.
+	url: String,
+	/// The slug of the article on which the link has been clicked.
+	from: Option<String>,
+}
+
+pub async fn outbound(
+	State(ctx): State<Arc<Context>>,
+	Query(params): Query<OutboundParams>,
+) -> Response {
+	let article = params
+		.from
+		.as_deref()
+		.and_then(|slug| ctx.get_article(slug))
+		.filter(|(a, _)| a.is_public());
+	let slug = article.map(|(a, _)| a.slug.as_str());
+	if !ctx.outbound.can_redirect(slug, &params.url) {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	ctx.outbound.record(slug, &params.url);
+	(StatusCode::FOUND, [(LOCATION, params.url)]).into_response()
+}
+
+/// The payload of a webmention.
+#[derive(Deserialize)]
+pub struct WebmentionPayload {
+	/// The URL of the page mentioning the target.
+	source: String,
+	/// The URL of the mentioned article.
+	target: String,
+}
+
+/// Receives a webmention. The mention is verified in the background.
+pub async fn webmention(
+	State(ctx): State<Arc<Context>>,
+	Form(payload): Form<WebmentionPayload>,
+) -> Response {
+	if !ctx.webmentions.is_enabled() {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	let source_valid =
+		payload.source.starts_with("http://") || payload.source.starts_with("https://");
+	if !source_valid || payload.source == payload.target {
+		return (StatusCode::BAD_REQUEST, "invalid source").into_response();
+	}
+	let Some(slug) = webmention::get_target_slug(&ctx, &payload.target) else {
+		return (StatusCode::BAD_REQUEST, "invalid target").into_response();
+	};
+	let slug = slug.to_owned();
+	tokio::spawn(webmention::receive(
+		ctx.clone(),
+		slug,
+		payload.source,
+		payload.target,
+	));
+	StatusCode::ACCEPTED.into_response()
+}
+
+/// The payload of a reaction.
+#[derive(Deserialize)]
+pub struct ReactionPayload {
+	/// The given reaction.
+	reaction: Reaction,
+}
+
+/// Adds a reaction to an article, then redirects back to it.
+pub async fn react(
+	State(ctx): State<Arc<Context>>,
+	ConnectInfo(addr): ConnectInfo<SocketAddr>,
+	Path(slug): Path<String>,
+	headers: HeaderMap,
+	Form(payload): Form<ReactionPayload>,
+) -> Response {
+	if !ctx.get_article(&slug).is_some_and(|(a, _)| a.is_public()) {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	let ip = client_ip(&ctx.trusted_proxies, &headers, addr);
+	ctx.reactions.add(&slug, payload.reaction, ip);
+	Redirect::to(&format!("/a/{slug}#reactions")).into_response()
+}
+
+/// Query parameters of the image resizing route.
+#[derive(Deserialize)]
+pub struct ImageParams {
+	/// The maximum width of the image, in pixels.
+	w: Option<u32>,
+	/// The output format.
+	format: Option<image::Format>,
+}
+
+pub async fn image(
+	State(ctx): State<Arc<Context>>,
+	Path(path): Path<PathBuf>,
+	Query(params): Query<ImageParams>,
+) -> Response {
+	if !files::is_valid_path(&path) {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	if params
+		.w
+		.is_some_and(|w| !image::ALLOWED_WIDTHS.contains(&w))
+	{
+		return (StatusCode::BAD_REQUEST, "width not allowed").into_response();
+	}
+	let source = ctx.assets_path.join(path);
+	if !source.is_file() {
+		return StatusCode::NOT_FOUND.into_response();
+	}
+	let cache_path = ctx.image_cache_path.clone();
+	let res = tokio::task::spawn_blocking(move || {
+		image::get_variant(&source, &cache_path, params.w, params.format)
+	})
+	.await
+	.unwrap();
+	match res {
+		Ok((data, format)) => {
+			let cache_control = format!("public, max-age={}", ctx.media_max_age);
+			(
+				[
+					(CONTENT_TYPE, format.mime().to_owned()),
+					(CACHE_CONTROL, cache_control),
+				],
+				data,
+			)
+				.into_response()
+		}
+		Err(error) => {
+			warn!(%error, "could not process image");
+			StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response()
+		}
+	}
 }
 
-pub async fn bio() -> Response {
-	Html(include_str!("../../pages/bio.html")).into_response()
+pub async fn bio(State(ctx): State<Arc<Context>>, theme: Theme) -> Response {
+	BioTemplate {
+		theme,
+		base_url: &ctx.base_url,
+	}
+	.into_response()
 }
 
-pub async fn legal() -> Response {
-	Html(include_str!("../../pages/legal.html")).into_response()
+pub async fn legal(State(ctx): State<Arc<Context>>, theme: Theme) -> Response {
+	LegalTemplate {
+		theme,
+		base_url: &ctx.base_url,
+	}
+	.into_response()
 }
 
 pub async fn sitemap(State(ctx): State<Arc<Context>>) -> Response {
 	let articles: String = ctx
-		.list_articles()
-		.filter(|a| a.is_public())
-		.map(|a| ArticleSitemap(a).to_string())
+		.query_articles(&ArticleQuery::default())
+		.map(|a| ArticleSitemap(a, &ctx.base_url).to_string())
 		.collect();
+	let base_url = &ctx.base_url;
 	let body = format!(
 		r#"<?xml version="1.0" encoding="UTF-8"?>
 <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-	<url><loc>https://blog.lenot.re/</loc></url>
-	<url><loc>https://blog.lenot.re/bio</loc></url>
-	<url><loc>https://blog.lenot.re/legal</loc></url>
+	<url><loc>{base_url}/</loc></url>
+	<url><loc>{base_url}/bio</loc></url>
+	<url><loc>{base_url}/legal</loc></url>
 {articles}
 </urlset>"#
 	);
 	([(CONTENT_TYPE, "application/xml")], body).into_response()
 }
 
-pub async fn rss(State(ctx): State<Arc<Context>>) -> Response {
+pub async fn rss(
+	State(ctx): State<Arc<Context>>,
+	ConnectInfo(addr): ConnectInfo<SocketAddr>,
+	Query(params): Query<FeedParams>,
+	headers: HeaderMap,
+) -> Response {
+	let user_agent = headers
+		.get(USER_AGENT)
+		.and_then(|h| h.to_str().ok())
+		.unwrap_or_default();
+	ctx.feed_subscribers
+		.record(client_ip(&ctx.trusted_proxies, &headers, addr), user_agent);
+	let query = ArticleQuery {
+		tag: params.tag().map(str::to_owned),
+		..Default::default()
+	};
 	let articles: String = ctx
-		.list_articles()
-		.filter(|a| a.is_public())
-		.map(|a| ArticleRss(a).to_string())
+		.query_articles(&query)
+		.map(|a| ArticleRss(a, &ctx.base_url).to_string())
 		.collect();
+	// Scheduled articles can become public after compilation
+	let last_modified = ctx
+		.query_articles(&query)
+		.map(|a| a.post_date)
+		.chain([ctx.compile_date])
+		.max()
+		.unwrap_or(ctx.compile_date);
+	let base_url = &ctx.base_url;
+	let url = encode_double_quoted_attribute(&params.url(base_url)).into_owned();
+	let title = encode_text(&params.title()).into_owned();
 	let body = format!(
-		r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel><atom:link href="https://blog.lenot.re/rss" rel="self" type="application/rss+xml" /><title>Maestro</title><link>https:/blog.lenot.re/</link><description>A blog about writing an operating system from scratch in Rust.</description>{articles}</channel></rss>"#
+		r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:dc="http://purl.org/dc/elements/1.1/"><channel><atom:link href="{url}" rel="self" type="application/rss+xml" /><title>{title}</title><link>{base_url}/</link><description>A blog about writing an operating system from scratch in Rust.</description>{articles}</channel></rss>"#
 	);
-	([(CONTENT_TYPE, "application/rss+xml")], body).into_response()
+	let etag = etag(body.as_bytes());
+	let cache_headers = [
+		(ETAG, etag.clone()),
+		(LAST_MODIFIED, http_date(&last_modified)),
+	];
+	if is_fresh(&headers, &etag, &last_modified) {
+		return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+	}
+	(cache_headers, [(CONTENT_TYPE, "application/rss+xml")], body).into_response()
 }
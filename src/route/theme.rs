@@ -0,0 +1,114 @@
+//! The color theme preference of visitors, stored in a cookie so that pages are rendered with
+//! the right theme from the start.
+
+use crate::{util::get_cookie, Context};
+use axum::{
+	async_trait,
+	extract::{FromRequestParts, Query},
+	http::{
+		header::{LOCATION, REFERER, SET_COOKIE},
+		request::Parts,
+		HeaderMap, StatusCode,
+	},
+	response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::{
+	convert::Infallible,
+	fmt::{self, Display, Formatter},
+	sync::Arc,
+};
+
+/// The name of the cookie holding the theme.
+const THEME_COOKIE: &str = "theme";
+
+/// A color theme.
+#[derive(Clone, Copy, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+	#[default]
+	Dark,
+	Light,
+	/// Follows the preference of the visitor's system.
+	Auto,
+}
+
+impl Theme {
+	/// Parses a theme from its name.
+	fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"dark" => Some(Self::Dark),
+			"light" => Some(Self::Light),
+			"auto" => Some(Self::Auto),
+			_ => None,
+		}
+	}
+
+	/// Returns the theme selected in the cookie of a request with the given headers.
+	pub fn from_headers(headers: &HeaderMap) -> Self {
+		get_cookie(headers, THEME_COOKIE)
+			.and_then(Self::from_name)
+			.unwrap_or_default()
+	}
+
+	/// Returns the theme that follows this one when toggling.
+	pub fn next(self) -> Self {
+		match self {
+			Self::Dark => Self::Light,
+			Self::Light => Self::Auto,
+			Self::Auto => Self::Dark,
+		}
+	}
+}
+
+impl Display for Theme {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let name = match self {
+			Self::Dark => "dark",
+			Self::Light => "light",
+			Self::Auto => "auto",
+		};
+		write!(f, "{name}")
+	}
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<Context>> for Theme {
+	type Rejection = Infallible;
+
+	async fn from_request_parts(
+		parts: &mut Parts,
+		_ctx: &Arc<Context>,
+	) -> Result<Self, Self::Rejection> {
+		Ok(Self::from_headers(&parts.headers))
+	}
+}
+
+/// Query parameters of the theme selection route.
+#[derive(Deserialize)]
+pub struct ThemeParams {
+	/// The selected theme.
+	set: Theme,
+}
+
+/// Sets the theme cookie, then redirects to the page the visitor comes from.
+pub async fn set(headers: HeaderMap, Query(params): Query<ThemeParams>) -> Response {
+	// Only redirect to a page of the blog
+	let location = headers
+		.get(REFERER)
+		.and_then(|h| h.to_str().ok())
+		.and_then(|r| r.split_once("://"))
+		.and_then(|(_, r)| r.find('/').map(|i| &r[i..]))
+		.filter(|path| !path.starts_with("//"))
+		.unwrap_or("/")
+		.to_owned();
+	let cookie = format!(
+		"{THEME_COOKIE}={theme}; Path=/; Max-Age=31536000; SameSite=Lax",
+		theme = params.set
+	);
+	(
+		StatusCode::SEE_OTHER,
+		[(SET_COOKIE, cookie), (LOCATION, location)],
+	)
+		.into_response()
+}
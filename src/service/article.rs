@@ -1,20 +1,63 @@
 //! This module handles articles.
 
+use crate::{
+	config::Config,
+	service::{i18n::Locale, outbound::OutboundTracker},
+};
 use anyhow::{bail, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Utc};
 use gateway_api::util::date_format;
 use lol_html::{element, HtmlRewriter};
-use pulldown_cmark::{html, Options, Parser};
-use serde::Deserialize;
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd, TextMergeStream};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::json;
 use std::{
+	collections::HashSet,
 	fmt,
 	fmt::{Display, Formatter, Write},
 	fs,
 	fs::DirEntry,
 	io,
-	path::Path,
 };
 use tracing::info;
+use utoipa::ToSchema;
+
+/// An author of articles.
+#[derive(Clone, Deserialize)]
+pub struct Author {
+	/// The author's name.
+	pub name: String,
+	/// The URL to the author's website.
+	pub url: Option<String>,
+	/// The URL to the author's avatar.
+	pub avatar: Option<String>,
+}
+
+impl Author {
+	/// Returns the slug identifying the author, derived from their name.
+	pub fn get_slug(&self) -> String {
+		let slug: String = self
+			.name
+			.chars()
+			.map(|c| {
+				if c.is_alphanumeric() {
+					c.to_ascii_lowercase()
+				} else {
+					'-'
+				}
+			})
+			.collect();
+		slug.split('-')
+			.filter(|s| !s.is_empty())
+			.collect::<Vec<_>>()
+			.join("-")
+	}
+
+	/// Returns the path to the author's page.
+	pub fn get_path(&self) -> String {
+		format!("/author/{}", self.get_slug())
+	}
+}
 
 /// An article.
 #[derive(Deserialize)]
@@ -30,16 +73,52 @@ pub struct Article {
 	/// The article's description.
 	pub description: String,
 	/// The URL to the cover image of the article.
+	///
+	/// If not specified, a preview image is generated from the article's title.
+	#[serde(default)]
 	pub cover_url: String,
 	/// The list of tags on the article.
 	#[serde(default)]
 	pub tags: Vec<String>,
+	/// The list of authors of the article.
+	#[serde(default)]
+	pub authors: Vec<Author>,
+	/// The identifier of the article on the previous version of the blog, used to redirect
+	/// legacy URLs.
+	pub legacy_id: Option<String>,
+	/// The license under which the article is published.
+	///
+	/// If not specified, the default license from the configuration is used.
+	pub license: Option<String>,
+
+	/// The article's content, in Markdown.
+	#[serde(skip)]
+	pub markdown: String,
 }
 
 impl Article {
-	/// Compiles all articles and returns them along with the resulting HTML, sorted by decreasing
-	/// post date.
-	pub fn compile_all(articles_path: &Path) -> Result<Vec<(Article, String)>> {
+	/// Creates an article from its `manifest`, in TOML, and its content in Markdown.
+	///
+	/// `slug` and `default_license` are used if the manifest does not specify them.
+	pub fn parse(
+		manifest: &str,
+		slug: &str,
+		markdown: String,
+		default_license: Option<&str>,
+	) -> Result<Self, toml::de::Error> {
+		let mut article: Self = toml::from_str(manifest)?;
+		if article.slug.is_empty() {
+			article.slug = slug.to_owned();
+		}
+		if article.license.is_none() {
+			article.license = default_license.map(str::to_owned);
+		}
+		article.markdown = markdown;
+		Ok(article)
+	}
+
+	/// Reads all articles, sorted by decreasing post date.
+	pub fn load_all(config: &Config) -> Result<Vec<Article>> {
 		let filter = |e: io::Result<DirEntry>| {
 			let e = e?;
 			if e.file_type()?.is_dir() && e.file_name() != ".git" {
@@ -48,108 +127,342 @@ impl Article {
 				Ok(None)
 			}
 		};
-		let articles: Result<Vec<(Self, String)>> = fs::read_dir(articles_path)?
+		let articles: Result<Vec<Self>> = fs::read_dir(&config.article_path)?
 			.filter_map(|e| filter(e).transpose())
 			.map(|e: io::Result<DirEntry>| {
 				let e = e?;
 				// Read metadata
 				let manifest_path = e.path().join("manifest.toml");
 				let manifest = fs::read_to_string(manifest_path)?;
-				let mut manifest: Self = match toml::from_str(&manifest) {
-					Ok(m) => m,
-					Err(err) => bail!(
-						"failed to read article {name}: {err}",
-						name = e.file_name().to_string_lossy()
-					),
-				};
-				if manifest.slug.is_empty() {
-					manifest.slug = e.file_name().to_string_lossy().into_owned();
+				let content_path = e.path().join("content.md");
+				let markdown = fs::read_to_string(content_path)?;
+				let name = e.file_name().to_string_lossy().into_owned();
+				match Self::parse(
+					&manifest,
+					&name,
+					markdown,
+					config.default_license.as_deref(),
+				) {
+					Ok(article) => Ok(article),
+					Err(err) => bail!("failed to read article {name}: {err}"),
 				}
+			})
+			.collect();
+		let mut articles = articles?;
+		articles.sort_unstable_by(|a1, a2| a1.post_date.cmp(&a2.post_date).reverse());
+		Ok(articles)
+	}
 
-				// Read and compile content
-				let content_path = e.path().join("content.md");
-				let content = fs::read_to_string(content_path)?;
-				let content = compile_content(&content);
+	/// Compiles all articles and returns them along with the resulting HTML, sorted by decreasing
+	/// post date.
+	///
+	/// Links to external pages go through `outbound` if it tracks them, and are registered as
+	/// allowed redirections from their article.
+	pub fn compile_all(
+		config: &Config,
+		outbound: &OutboundTracker,
+	) -> Result<Vec<(Article, String)>> {
+		let articles = Self::load_all(config)?
+			.into_iter()
+			.map(|article| {
+				let (content, links) = article.compile(outbound);
+				outbound.set_links(&article.slug, links);
 				info!(
-					title = manifest.title,
-					public = manifest.is_public(),
+					title = article.title,
+					public = article.is_public(),
 					"compiled article"
 				);
-
-				Ok((manifest, content))
+				(article, content)
 			})
 			.collect();
-		let mut articles = articles?;
-		articles.sort_unstable_by(|(a1, _), (a2, _)| a1.post_date.cmp(&a2.post_date).reverse());
 		Ok(articles)
 	}
 
+	/// Compiles the article's content from Markdown into HTML.
+	///
+	/// Links to external pages go through `outbound` if it tracks them. The function returns the
+	/// HTML along with the URLs of these links.
+	pub fn compile(&self, outbound: &OutboundTracker) -> (String, HashSet<String>) {
+		compile_content(outbound, &self.slug, &self.markdown)
+	}
+
 	/// Returns the path to the article.
 	pub fn get_path(&self) -> String {
 		format!("/a/{}", self.slug)
 	}
 
-	/// Returns the URL of the article.
-	pub fn get_url(&self) -> String {
-		format!("https://blog.lenot.re/a/{}", self.slug)
+	/// Returns the URL of the article, on the blog at `base_url`.
+	pub fn get_url(&self, base_url: &str) -> String {
+		format!("{base_url}{}", self.get_path())
 	}
 
 	/// Tells whether the article is public.
 	pub fn is_public(&self) -> bool {
 		self.post_date <= Utc::now()
 	}
+
+	/// Returns the article's metadata as a JSON-LD document, `base_url` being the URL of the blog.
+	pub fn get_json_ld(&self, base_url: &str) -> String {
+		let doc = json!({
+			"@context": "https://schema.org",
+			"@type": "BlogPosting",
+			"headline": self.title,
+			"description": self.description,
+			"image": self.cover_url,
+			"url": self.get_url(base_url),
+			"datePublished": self.post_date.to_rfc3339(),
+			"keywords": self.tags,
+			"license": self.license,
+			"author": self.authors.iter().map(|a| json!({
+				"@type": "Person",
+				"name": a.name,
+				"url": a.url,
+			})).collect::<Vec<_>>(),
+		});
+		// Prevent the document from closing the enclosing `script` tag
+		doc.to_string().replace("</", "<\\/")
+	}
+}
+
+/// The order in which articles are listed.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArticleSort {
+	/// By decreasing relevance to the searched text, or by decreasing post date if there is none.
+	#[default]
+	Relevance,
+	/// By decreasing post date.
+	Date,
+	/// By decreasing number of reactions.
+	Popular,
 }
 
-/// Display an article as an element on the index page.
-pub struct ArticleListHtml<'a>(pub &'a Article);
+/// A set of filters to select articles.
+///
+/// Filters that are not specified match every article.
+#[derive(Default, Deserialize)]
+pub struct ArticleQuery {
+	/// Text to search in the article's title, description, tags and content.
+	#[serde(default)]
+	pub q: Option<String>,
+	/// A tag the article must have.
+	#[serde(default)]
+	pub tag: Option<String>,
+	/// The year in which the article has been posted.
+	#[serde(default, deserialize_with = "deserialize_year")]
+	pub year: Option<i32>,
+	/// The slug of an author of the article.
+	#[serde(default)]
+	pub author: Option<String>,
+	/// The order in which matching articles are listed.
+	#[serde(default)]
+	pub sort: ArticleSort,
+	/// If `true`, non-public articles are included.
+	#[serde(skip)]
+	pub private: bool,
+}
+
+/// Deserializes an optional year, an empty string meaning the filter is not specified.
+fn deserialize_year<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i32>, D::Error> {
+	Option::<String>::deserialize(deserializer)?
+		.filter(|y| !y.is_empty())
+		.map(|y| y.parse().map_err(de::Error::custom))
+		.transpose()
+}
 
-impl ArticleListHtml<'_> {
-	/// Returns the HTML representing the article's tags.
-	fn get_tags_html(&self) -> Result<String, fmt::Error> {
-		let mut html = String::new();
-		self.0
-			.tags
-			.iter()
-			.try_for_each(|tag| write!(html, r#"<li class="tag">{tag}</li>"#))?;
-		Ok(html)
+impl ArticleQuery {
+	/// Tells whether the given article, with its compiled `content`, matches the query.
+	pub fn matches(&self, article: &Article, content: &str) -> bool {
+		if !self.private && !article.is_public() {
+			return false;
+		}
+		if let Some(tag) = self.tag.as_deref().filter(|t| !t.is_empty()) {
+			if !article.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+				return false;
+			}
+		}
+		if let Some(year) = self.year {
+			if article.post_date.year() != year {
+				return false;
+			}
+		}
+		if let Some(author) = self.author.as_deref().filter(|a| !a.is_empty()) {
+			if !article.authors.iter().any(|a| a.get_slug() == author) {
+				return false;
+			}
+		}
+		if let Some(q) = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+			let q = q.to_lowercase();
+			let found = article.title.to_lowercase().contains(&q)
+				|| article.description.to_lowercase().contains(&q)
+				|| article.tags.iter().any(|t| t.to_lowercase().contains(&q))
+				|| content.to_lowercase().contains(&q);
+			if !found {
+				return false;
+			}
+		}
+		true
 	}
 }
 
-impl Display for ArticleListHtml<'_> {
+/// Display the byline of an article on its page.
+pub struct ArticleAuthorsHtml<'a>(pub &'a Article);
+
+impl Display for ArticleAuthorsHtml<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if self.0.authors.is_empty() {
+			return Ok(());
+		}
+		write!(f, r#"<ul class="authors">"#)?;
+		for author in &self.0.authors {
+			write!(f, "<li>{}</li>", AuthorHtml(author))?;
+		}
+		write!(f, "</ul>")
+	}
+}
+
+/// Display an author as a link to their page, along with their avatar.
+pub struct AuthorHtml<'a>(pub &'a Author);
+
+impl Display for AuthorHtml<'_> {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
 		write!(
 			f,
-			r#"<a href="{path}">
-				<div class="article-element">
-					<img class="article-cover" src="{cover_url}" alt="{title}"></img>
-					<div class="article-element-content">
-						<h3>{title}</h3>
-						<ul class="tags">
-							<li class="date"><span id="date">{post_date}</span></li>
-							{tags}
-						</ul>
-						<p>
-							{desc}
-						</p>
-					</div>
+			r#"<a class="author" href="{path}">"#,
+			path = self.0.get_path()
+		)?;
+		if let Some(avatar) = &self.0.avatar {
+			write!(
+				f,
+				r#"<img class="author-avatar" src="{avatar}" alt="{name}"></img>"#,
+				name = self.0.name
+			)?;
+		}
+		write!(f, "{name}</a>", name = self.0.name)
+	}
+}
+
+/// Returns the HTML representing the article's tags, in a list of articles, in the locale `t`.
+fn get_list_tags_html(article: &Article, t: Locale) -> Result<String, fmt::Error> {
+	let mut html = String::new();
+	if !article.is_public() {
+		write!(html, r#"<li class="tag private">{}</li>"#, t.get("private"))?;
+	}
+	article
+		.tags
+		.iter()
+		.try_for_each(|tag| write!(html, r#"<li class="tag">{tag}</li>"#))?;
+	Ok(html)
+}
+
+/// Writes an article as an element of a list of articles, with the given HTML `desc`, in the
+/// locale `t`.
+fn write_list_element(
+	f: &mut Formatter<'_>,
+	article: &Article,
+	desc: &str,
+	t: Locale,
+) -> fmt::Result {
+	write!(
+		f,
+		r#"<a href="{path}">
+			<div class="article-element">
+				<img class="article-cover" src="{cover_url}" alt="{title}"></img>
+				<div class="article-element-content">
+					<h3>{title}</h3>
+					<ul class="tags">
+						<li class="date"><span id="date">{post_date}</span></li>
+						{tags}
+					</ul>
+					<p>
+						{desc}
+					</p>
 				</div>
-			</a>"#,
-			path = self.0.get_path(),
-			cover_url = self.0.cover_url,
-			title = self.0.title,
-			post_date = self.0.post_date.to_rfc3339(),
-			tags = self.get_tags_html()?,
-			desc = self.0.description,
-		)
+			</div>
+		</a>"#,
+		path = article.get_path(),
+		cover_url = article.cover_url,
+		title = article.title,
+		post_date = article.post_date.to_rfc3339(),
+		tags = get_list_tags_html(article, t)?,
+	)
+}
+
+/// Display an article as an element on the index page, in the given locale.
+pub struct ArticleListHtml<'a>(pub &'a Article, pub Locale<'a>);
+
+impl Display for ArticleListHtml<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write_list_element(f, self.0, &self.0.description, self.1)
 	}
 }
 
-/// Display an article as a sitemap element.
-pub struct ArticleSitemap<'a>(pub &'a Article);
+/// Display an article as a search result, with an HTML excerpt of its content matching the
+/// search, in the given locale.
+pub struct ArticleSearchHtml<'a>(pub &'a Article, pub String, pub Locale<'a>);
+
+impl Display for ArticleSearchHtml<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		if self.1.is_empty() {
+			write_list_element(f, self.0, &self.0.description, self.2)
+		} else {
+			write_list_element(f, self.0, &self.1, self.2)
+		}
+	}
+}
+
+/// Serializable representation of an article, for the JSON API.
+#[derive(Serialize, ToSchema)]
+pub struct ArticleJson<'a> {
+	/// The article's slug.
+	pub slug: &'a str,
+	/// The URL of the article.
+	pub url: String,
+	/// The article's title.
+	pub title: &'a str,
+	/// Timestamp at which the article has been posted, in RFC 3339 format.
+	pub post_date: String,
+	/// The article's description.
+	pub description: &'a str,
+	/// The URL to the cover image of the article.
+	pub cover_url: &'a str,
+	/// The list of tags on the article.
+	#[schema(value_type = Vec<String>)]
+	pub tags: &'a [String],
+	/// The names of the article's authors.
+	pub authors: Vec<&'a str>,
+	/// The license under which the article is published.
+	pub license: Option<&'a str>,
+	/// The compiled HTML content of the article, if requested.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub content: Option<&'a str>,
+}
+
+impl<'a> ArticleJson<'a> {
+	/// Creates a new instance from the given article, on the blog at `base_url`. If `content` is
+	/// set, it is included in the output.
+	pub fn new(article: &'a Article, base_url: &str, content: Option<&'a str>) -> Self {
+		Self {
+			slug: &article.slug,
+			url: article.get_url(base_url),
+			title: &article.title,
+			post_date: article.post_date.to_rfc3339(),
+			description: &article.description,
+			cover_url: &article.cover_url,
+			tags: &article.tags,
+			authors: article.authors.iter().map(|a| a.name.as_str()).collect(),
+			license: article.license.as_deref(),
+			content,
+		}
+	}
+}
+
+/// Display an article as a sitemap element, along with the URL of the blog.
+pub struct ArticleSitemap<'a>(pub &'a Article, pub &'a str);
 
 impl Display for ArticleSitemap<'_> {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-		let url = self.0.get_url();
+		let url = self.0.get_url(self.1);
 		let date = self.0.post_date.format("%Y-%m-%d");
 		write!(
 			f,
@@ -158,31 +471,91 @@ impl Display for ArticleSitemap<'_> {
 	}
 }
 
-/// Display an article as an RSS element.
-pub struct ArticleRss<'a>(pub &'a Article);
+/// Display an article as an RSS element, along with the URL of the blog.
+pub struct ArticleRss<'a>(pub &'a Article, pub &'a str);
 
 impl Display for ArticleRss<'_> {
 	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
 		write!(
 			f,
-			"<item><guid>{url}</guid><title>{title}</title><link>{url}</link><pubDate>{post_date}</pubDate><description>{desc}</description></item>",
-			url = self.0.get_url(),
+			"<item><guid>{url}</guid><title>{title}</title><link>{url}</link><pubDate>{post_date}</pubDate><description>{desc}</description>",
+			url = self.0.get_url(self.1),
 			title = self.0.title,
 			post_date = self.0.post_date.to_rfc2822(),
 			desc = self.0.description
-		)
+		)?;
+		for author in &self.0.authors {
+			write!(f, "<dc:creator>{name}</dc:creator>", name = author.name)?;
+		}
+		if let Some(license) = &self.0.license {
+			write!(f, "<dc:rights>{license}</dc:rights>")?;
+		}
+		write!(f, "</item>")
 	}
 }
 
-/// Compiles the given content from Markdown into HTML.
-fn compile_content(content: &str) -> String {
+/// Replaces emoji shortcodes (such as `:crab:`) in the given text with the corresponding emoji.
+///
+/// Shortcodes that do not correspond to any emoji are left untouched.
+fn expand_shortcodes(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(start) = rest.find(':') {
+		out.push_str(&rest[..start]);
+		let after = &rest[start + 1..];
+		let emoji = after
+			.find(':')
+			.map(|end| &after[..end])
+			.filter(|code| {
+				!code.is_empty()
+					&& code
+						.chars()
+						.all(|c| c.is_ascii_alphanumeric() || "_+-".contains(c))
+			})
+			.and_then(|code| Some((code, emojis::get_by_shortcode(code)?)));
+		match emoji {
+			Some((code, emoji)) => {
+				out.push_str(emoji.as_str());
+				rest = &after[code.len() + 1..];
+			}
+			None => {
+				out.push(':');
+				rest = after;
+			}
+		}
+	}
+	out.push_str(rest);
+	out
+}
+
+/// Compiles the given content of the article with the given `slug` from Markdown into HTML.
+///
+/// The function returns the HTML along with the URLs of the links going through `outbound`.
+fn compile_content(
+	outbound: &OutboundTracker,
+	slug: &str,
+	content: &str,
+) -> (String, HashSet<String>) {
 	// Compile to HTML
-	let parser = Parser::new_ext(&content, Options::all());
+	let mut in_code_block = false;
+	let parser = TextMergeStream::new(Parser::new_ext(content, Options::all())).map(|event| {
+		match event {
+			Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+			Event::End(TagEnd::CodeBlock) => in_code_block = false,
+			// Expand emoji shortcodes, except in code
+			Event::Text(text) if !in_code_block => {
+				return Event::Text(expand_shortcodes(&text).into());
+			}
+			_ => {}
+		}
+		event
+	});
 	let mut content = String::new();
 	html::push_html(&mut content, parser);
 
 	// Rewrite HTML
 	let mut output = vec![];
+	let mut links = HashSet::new();
 	let mut rewriter = HtmlRewriter::new(
 		lol_html::Settings {
 			element_content_handlers: vec![
@@ -202,6 +575,17 @@ fn compile_content(content: &str) -> String {
 					}
 					Ok(())
 				}),
+				// Track clicks on external links
+				element!("a[href]", |e| {
+					let href = e.get_attribute("href").unwrap();
+					if outbound.is_tracked(&href) {
+						let query =
+							serde_urlencoded::to_string([("url", &*href), ("from", slug)])?;
+						e.set_attribute("href", &format!("/out?{query}")).unwrap();
+						links.insert(href);
+					}
+					Ok(())
+				}),
 			],
 			..lol_html::Settings::default()
 		},
@@ -210,5 +594,5 @@ fn compile_content(content: &str) -> String {
 	rewriter.write(content.as_bytes()).unwrap();
 	rewriter.end().unwrap();
 
-	String::from_utf8(output).unwrap()
+	(String::from_utf8(output).unwrap(), links)
 }
@@ -0,0 +1,86 @@
+//! This module implements the edition of articles by the administrator.
+//!
+//! Articles are stored in a git repository, in which each article has its own directory. Changes
+//! are committed to the repository so that they are not lost nor overwritten by the authoring
+//! workflow.
+
+use anyhow::{bail, Result};
+use std::{io, path::Path, process::Stdio};
+use tokio::{fs, process::Command};
+
+/// The name of the file holding the manifest of an article.
+pub const MANIFEST_FILE: &str = "manifest.toml";
+/// The name of the file holding the content of an article.
+pub const CONTENT_FILE: &str = "content.md";
+
+/// Returns the names of the article directories in `root`, sorted alphabetically.
+pub async fn list(root: &Path) -> io::Result<Vec<String>> {
+	let mut names = vec![];
+	let mut entries = fs::read_dir(root).await?;
+	while let Some(entry) = entries.next_entry().await? {
+		let name = entry.file_name().to_string_lossy().into_owned();
+		if entry.file_type().await?.is_dir() && !name.starts_with('.') {
+			names.push(name);
+		}
+	}
+	names.sort_unstable();
+	Ok(names)
+}
+
+/// Reads the manifest and content of the article in the directory `name`.
+///
+/// Missing files are read as empty, so that new articles can be created.
+pub async fn read(root: &Path, name: &str) -> io::Result<(String, String)> {
+	let read = |file| async move {
+		match fs::read_to_string(root.join(name).join(file)).await {
+			Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+			res => res,
+		}
+	};
+	Ok((read(MANIFEST_FILE).await?, read(CONTENT_FILE).await?))
+}
+
+/// Runs git in the repository at `root` with the given arguments and returns its exit status.
+async fn git(root: &Path, args: &[&str]) -> Result<bool> {
+	let output = Command::new("git")
+		.arg("-C")
+		.arg(root)
+		.args(args)
+		.stdin(Stdio::null())
+		.output()
+		.await?;
+	if !output.status.success() && !output.stderr.is_empty() {
+		bail!(
+			"git {}: {}",
+			args[0],
+			String::from_utf8_lossy(&output.stderr).trim()
+		);
+	}
+	Ok(output.status.success())
+}
+
+/// Writes the manifest and content of the article in the directory `name`, then commits them to
+/// the repository with the given `message`.
+///
+/// If the files did not change, no commit is made and the function returns `false`.
+pub async fn save(
+	root: &Path,
+	name: &str,
+	manifest: &str,
+	content: &str,
+	message: &str,
+) -> Result<bool> {
+	let dir = root.join(name);
+	fs::create_dir_all(&dir).await?;
+	fs::write(dir.join(MANIFEST_FILE), manifest).await?;
+	fs::write(dir.join(CONTENT_FILE), content).await?;
+	git(root, &["add", "--", name]).await?;
+	// Exits with a failure status if there are staged changes
+	if git(root, &["diff", "--cached", "--quiet", "--", name]).await? {
+		return Ok(false);
+	}
+	if !git(root, &["commit", "--quiet", "-m", message, "--", name]).await? {
+		bail!("git commit failed");
+	}
+	Ok(true)
+}
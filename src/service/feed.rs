@@ -0,0 +1,207 @@
+//! This module describes the feeds of the blog and estimates the number of subscribers to the RSS
+//! feed.
+
+use axum::http::Uri;
+use html_escape::encode_double_quoted_attribute;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+	hash::{BuildHasher, Hash, Hasher, RandomState},
+	net::IpAddr,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+/// The duration after which a reader that did not fetch the feed is not counted anymore.
+const READER_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// The interval at which expired readers are removed.
+const READER_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The maximum number of readers remembered. Beyond that, new readers are not counted until
+/// others expire.
+const MAX_READERS: usize = 100_000;
+/// Aggregators whose reported number of subscribers is accepted, as named in their user agent.
+///
+/// Other names are ignored so that arbitrary clients cannot add entries to the estimation.
+const KNOWN_AGGREGATORS: &[&str] = &[
+	"BazQux",
+	"Feedbin",
+	"Feedly",
+	"Feedspot",
+	"Inoreader",
+	"NewsBlur",
+	"theoldreader.com",
+];
+
+/// The title of the RSS feed.
+pub const TITLE: &str = "Maestro";
+
+/// Query parameters of the RSS feed.
+#[derive(Default, Deserialize)]
+pub struct FeedParams {
+	/// If set, only articles with this tag are included.
+	#[serde(default)]
+	pub tag: Option<String>,
+}
+
+impl FeedParams {
+	/// Returns the tag to filter on, if any.
+	pub fn tag(&self) -> Option<&str> {
+		self.tag.as_deref().filter(|t| !t.is_empty())
+	}
+
+	/// Returns the title of the feed.
+	pub fn title(&self) -> String {
+		match self.tag() {
+			Some(tag) => format!("{TITLE} - #{tag}"),
+			None => TITLE.to_owned(),
+		}
+	}
+
+	/// Returns the URL of the feed.
+	pub fn url(&self, base_url: &str) -> String {
+		match self.tag() {
+			Some(tag) => {
+				let query = serde_urlencoded::to_string([("tag", tag)]).unwrap_or_default();
+				format!("{base_url}/rss?{query}")
+			}
+			None => format!("{base_url}/rss"),
+		}
+	}
+}
+
+/// Display the link advertising a feed in the head of HTML pages.
+pub struct FeedLinkHtml<'a>(pub &'a FeedParams, pub &'a str);
+
+impl Display for FeedLinkHtml<'_> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			r#"<link rel="alternate" type="application/rss+xml" title="{title}" href="{url}" />"#,
+			title = encode_double_quoted_attribute(&self.0.title()),
+			url = encode_double_quoted_attribute(&self.0.url(self.1)),
+		)
+	}
+}
+
+/// Returns the feeds relevant to the page at the given URI, starting with the main feed.
+///
+/// Pages listing the articles of a tag also advertise the feed of that tag.
+pub fn get_feeds(uri: &Uri) -> Vec<FeedParams> {
+	let mut feeds = vec![FeedParams::default()];
+	if uri.path() == "/search" {
+		let params: FeedParams = uri
+			.query()
+			.and_then(|q| serde_urlencoded::from_str(q).ok())
+			.unwrap_or_default();
+		if params.tag().is_some() {
+			feeds.push(params);
+		}
+	}
+	feeds
+}
+
+/// Estimation of the number of subscribers to the feed.
+#[derive(Serialize, ToSchema)]
+pub struct FeedEstimate {
+	/// The total estimated number of subscribers.
+	pub total: u64,
+	/// Subscribers reported by aggregators (such as Feedly), by aggregator name.
+	pub aggregators: HashMap<String, u64>,
+	/// The number of distinct individual readers.
+	pub readers: u64,
+}
+
+/// Parses the number of subscribers reported in a feed fetcher's user agent.
+///
+/// Aggregators usually report it as `<N> subscribers` (examples: Feedly, NewsBlur, Inoreader).
+/// On success, the function returns the name of the aggregator and the number of subscribers.
+///
+/// If the aggregator is not in [`KNOWN_AGGREGATORS`], the function returns `None`.
+fn parse_user_agent(user_agent: &str) -> Option<(&'static str, u64)> {
+	let words: Vec<&str> = user_agent
+		.split(|c: char| c.is_whitespace() || c == ';' || c == '(' || c == ')')
+		.filter(|w| !w.is_empty())
+		.collect();
+	let count = words.windows(2).find_map(|w| {
+		if w[1].starts_with("subscriber") {
+			w[0].parse().ok()
+		} else {
+			None
+		}
+	})?;
+	let name = user_agent.split(['/', ' ']).next()?;
+	let name = KNOWN_AGGREGATORS
+		.iter()
+		.copied()
+		.find(|n| n.eq_ignore_ascii_case(name))?;
+	Some((name, count))
+}
+
+/// Tracks fetches of the feed to estimate its number of subscribers.
+pub struct FeedSubscribers {
+	/// The hasher used to identify individual readers.
+	hasher: RandomState,
+	/// The last number of subscribers reported by each aggregator.
+	aggregators: Mutex<HashMap<String, u64>>,
+	/// Hashed individual readers, along with the last time they have fetched the feed.
+	readers: Mutex<HashMap<u64, Instant>>,
+	/// The last time expired readers have been removed.
+	last_prune: Mutex<Instant>,
+}
+
+impl Default for FeedSubscribers {
+	fn default() -> Self {
+		Self {
+			hasher: RandomState::new(),
+			aggregators: Default::default(),
+			readers: Default::default(),
+			last_prune: Mutex::new(Instant::now()),
+		}
+	}
+}
+
+impl FeedSubscribers {
+	/// Records a fetch of the feed by the given client.
+	pub fn record(&self, ip: IpAddr, user_agent: &str) {
+		if let Some((name, count)) = parse_user_agent(user_agent) {
+			self.aggregators
+				.lock()
+				.unwrap()
+				.insert(name.to_owned(), count);
+			return;
+		}
+		let mut hasher = self.hasher.build_hasher();
+		ip.hash(&mut hasher);
+		user_agent.hash(&mut hasher);
+		let id = hasher.finish();
+		let now = Instant::now();
+		let mut readers = self.readers.lock().unwrap();
+		// Prune periodically rather than on each fetch
+		let mut last_prune = self.last_prune.lock().unwrap();
+		if now.duration_since(*last_prune) >= READER_PRUNE_INTERVAL {
+			readers.retain(|_, last| now.duration_since(*last) < READER_TTL);
+			*last_prune = now;
+		}
+		if readers.len() < MAX_READERS || readers.contains_key(&id) {
+			readers.insert(id, now);
+		}
+	}
+
+	/// Returns the current estimation.
+	pub fn estimate(&self) -> FeedEstimate {
+		let aggregators = self.aggregators.lock().unwrap().clone();
+		let readers = {
+			let now = Instant::now();
+			let mut readers = self.readers.lock().unwrap();
+			readers.retain(|_, last| now.duration_since(*last) < READER_TTL);
+			readers.len() as u64
+		};
+		FeedEstimate {
+			total: aggregators.values().sum::<u64>() + readers,
+			aggregators,
+			readers,
+		}
+	}
+}
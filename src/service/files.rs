@@ -0,0 +1,90 @@
+//! This module manages the files of the article assets directory, uploaded by the administrator.
+
+use crate::util::list_files;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+	fs, io,
+	path::{Component, Path, PathBuf},
+};
+use utoipa::ToSchema;
+
+/// The allowed MIME types of uploaded files, along with their allowed extensions.
+const ALLOWED_TYPES: &[(&str, &[&str])] = &[
+	("application/pdf", &["pdf"]),
+	("image/avif", &["avif"]),
+	("image/gif", &["gif"]),
+	("image/jpeg", &["jpg", "jpeg"]),
+	("image/png", &["png"]),
+	("image/svg+xml", &["svg"]),
+	("image/webp", &["webp"]),
+	("video/mp4", &["mp4"]),
+	("video/webm", &["webm"]),
+];
+
+/// Information about a stored file.
+#[derive(Serialize, ToSchema)]
+pub struct FileEntry {
+	/// The path of the file, relative to the assets directory.
+	#[schema(value_type = String)]
+	pub path: PathBuf,
+	/// The URL at which the file is served.
+	pub url: String,
+	/// The size of the file, in bytes.
+	pub size: u64,
+	/// The last modification date of the file.
+	pub modified: Option<DateTime<Utc>>,
+}
+
+/// Returns the URL at which the file at the given relative `path` is served.
+pub fn get_url(path: &Path) -> String {
+	format!("/assets/article/{}", path.display())
+}
+
+/// Checks that the given relative `path` stays inside the assets directory.
+pub fn is_valid_path(path: &Path) -> bool {
+	path.components().count() > 0 && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Checks the name of an uploaded file, returning it if valid.
+///
+/// Only names made of ASCII alphanumeric characters, `-`, `_` and `.` are accepted, to avoid
+/// ambiguous URLs and hidden files.
+pub fn check_name(name: &str) -> Option<&str> {
+	let valid = !name.is_empty()
+		&& !name.starts_with('.')
+		&& name
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+	valid.then_some(name)
+}
+
+/// Tells whether a file with the given `name` and MIME type can be uploaded.
+pub fn is_allowed_type(name: &str, mime: &str) -> bool {
+	let Some((_, ext)) = name.rsplit_once('.') else {
+		return false;
+	};
+	let ext = ext.to_ascii_lowercase();
+	ALLOWED_TYPES
+		.iter()
+		.any(|(m, exts)| *m == mime && exts.contains(&ext.as_str()))
+}
+
+/// Returns the list of files in the assets directory at `root`, sorted by path.
+pub fn list(root: &Path) -> io::Result<Vec<FileEntry>> {
+	let mut paths = vec![];
+	list_files(root, root, &mut paths)?;
+	paths.sort_unstable();
+	paths
+		.into_iter()
+		.map(|path| {
+			let metadata = fs::metadata(root.join(&path))?;
+			Ok(FileEntry {
+				url: get_url(&path),
+				size: metadata.len(),
+				modified: metadata.modified().ok().map(DateTime::from),
+				path,
+			})
+		})
+		.collect()
+}
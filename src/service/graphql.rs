@@ -0,0 +1,255 @@
+//! This module implements the GraphQL API, giving read-only access to public data.
+
+use crate::{
+	service::article::{Article, ArticleQuery, Author},
+	Context,
+};
+use async_graphql::{
+	EmptyMutation, EmptySubscription, InputObject, Object, Result, Schema, SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// The maximum depth of a query.
+const MAX_DEPTH: usize = 8;
+/// The maximum complexity of a query.
+const MAX_COMPLEXITY: usize = 512;
+/// The default number of articles returned by a query.
+const DEFAULT_FIRST: usize = 20;
+/// The maximum number of articles returned by a query.
+const MAX_FIRST: usize = 100;
+
+/// The schema of the GraphQL API.
+pub type BlogSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Builds the schema of the GraphQL API.
+///
+/// Queries are executed with the server's [`Context`] as data.
+pub fn build_schema() -> BlogSchema {
+	Schema::build(Query, EmptyMutation, EmptySubscription)
+		.limit_depth(MAX_DEPTH)
+		.limit_complexity(MAX_COMPLEXITY)
+		.finish()
+}
+
+/// Returns the server's context from the context of the query.
+fn get_ctx<'ctx>(ctx: &async_graphql::Context<'ctx>) -> Result<&'ctx Context> {
+	Ok(ctx.data::<Arc<Context>>()?)
+}
+
+/// The root of queries.
+pub struct Query;
+
+#[Object]
+impl Query {
+	/// Public articles, ordered by decreasing post date.
+	async fn articles<'ctx>(
+		&self,
+		ctx: &async_graphql::Context<'ctx>,
+		#[graphql(default)] filter: ArticleFilter,
+		#[graphql(desc = "The maximum number of articles to return")] first: Option<usize>,
+		#[graphql(desc = "The number of articles to skip")] offset: Option<usize>,
+	) -> Result<Vec<ArticleNode<'ctx>>> {
+		let blog = get_ctx(ctx)?;
+		let query = ArticleQuery {
+			q: filter.q,
+			tag: filter.tag,
+			year: filter.year,
+			author: filter.author,
+			private: false,
+			..Default::default()
+		};
+		let first = first.unwrap_or(DEFAULT_FIRST).min(MAX_FIRST);
+		let articles = blog
+			.articles
+			.iter()
+			.filter(|(a, content)| query.matches(a, content))
+			.skip(offset.unwrap_or(0))
+			.take(first)
+			.map(|(article, content)| ArticleNode {
+				article,
+				content,
+				base_url: &blog.base_url,
+			})
+			.collect();
+		Ok(articles)
+	}
+
+	/// The public article with the given slug.
+	async fn article<'ctx>(
+		&self,
+		ctx: &async_graphql::Context<'ctx>,
+		slug: String,
+	) -> Result<Option<ArticleNode<'ctx>>> {
+		let blog = get_ctx(ctx)?;
+		let article =
+			blog.get_article(&slug)
+				.filter(|(a, _)| a.is_public())
+				.map(|(article, content)| ArticleNode {
+					article,
+					content,
+					base_url: &blog.base_url,
+				});
+		Ok(article)
+	}
+
+	/// The tags of public articles, sorted by name.
+	async fn tags(&self, ctx: &async_graphql::Context<'_>) -> Result<Vec<Tag>> {
+		let blog = get_ctx(ctx)?;
+		let mut tags = BTreeMap::<&str, usize>::new();
+		for tag in blog
+			.articles
+			.iter()
+			.filter(|(a, _)| a.is_public())
+			.flat_map(|(a, _)| &a.tags)
+		{
+			*tags.entry(tag).or_default() += 1;
+		}
+		let tags = tags
+			.into_iter()
+			.map(|(name, count)| Tag {
+				name: name.to_owned(),
+				count,
+			})
+			.collect();
+		Ok(tags)
+	}
+
+	/// The authors of public articles.
+	async fn authors<'ctx>(
+		&self,
+		ctx: &async_graphql::Context<'ctx>,
+	) -> Result<Vec<AuthorNode<'ctx>>> {
+		let blog = get_ctx(ctx)?;
+		let mut authors: Vec<&Author> = vec![];
+		for author in blog
+			.articles
+			.iter()
+			.filter(|(a, _)| a.is_public())
+			.flat_map(|(a, _)| &a.authors)
+		{
+			if !authors.iter().any(|a| a.name == author.name) {
+				authors.push(author);
+			}
+		}
+		Ok(authors.into_iter().map(AuthorNode).collect())
+	}
+}
+
+/// Filters on articles.
+#[derive(Default, InputObject)]
+pub struct ArticleFilter {
+	/// Text to search in the article's title, description, tags and content.
+	q: Option<String>,
+	/// A tag the article must have.
+	tag: Option<String>,
+	/// The year in which the article has been posted.
+	year: Option<i32>,
+	/// The slug of an author of the article.
+	author: Option<String>,
+}
+
+/// An article.
+pub struct ArticleNode<'a> {
+	/// The article.
+	article: &'a Article,
+	/// The compiled content of the article.
+	content: &'a str,
+	/// The public URL of the blog.
+	base_url: &'a str,
+}
+
+#[Object(name = "Article")]
+impl ArticleNode<'_> {
+	/// The article's slug.
+	async fn slug(&self) -> &str {
+		&self.article.slug
+	}
+
+	/// The URL of the article.
+	async fn url(&self) -> String {
+		self.article.get_url(self.base_url)
+	}
+
+	/// The article's title.
+	async fn title(&self) -> &str {
+		&self.article.title
+	}
+
+	/// Timestamp at which the article has been posted.
+	async fn post_date(&self) -> DateTime<Utc> {
+		self.article.post_date
+	}
+
+	/// The article's description.
+	async fn description(&self) -> &str {
+		&self.article.description
+	}
+
+	/// The URL to the cover image of the article.
+	async fn cover_url(&self) -> &str {
+		&self.article.cover_url
+	}
+
+	/// The list of tags on the article.
+	async fn tags(&self) -> &[String] {
+		&self.article.tags
+	}
+
+	/// The authors of the article.
+	async fn authors(&self) -> Vec<AuthorNode<'_>> {
+		self.article.authors.iter().map(AuthorNode).collect()
+	}
+
+	/// The license under which the article is published.
+	async fn license(&self) -> Option<&str> {
+		self.article.license.as_deref()
+	}
+
+	/// The compiled HTML content of the article.
+	#[graphql(complexity = "10 * child_complexity")]
+	async fn content(&self) -> &str {
+		self.content
+	}
+
+	/// The content of the article, in Markdown.
+	#[graphql(complexity = "10 * child_complexity")]
+	async fn markdown(&self) -> &str {
+		&self.article.markdown
+	}
+}
+
+/// An author of articles.
+pub struct AuthorNode<'a>(&'a Author);
+
+#[Object(name = "Author")]
+impl AuthorNode<'_> {
+	/// The slug identifying the author.
+	async fn slug(&self) -> String {
+		self.0.get_slug()
+	}
+
+	/// The author's name.
+	async fn name(&self) -> &str {
+		&self.0.name
+	}
+
+	/// The URL to the author's website.
+	async fn url(&self) -> Option<&str> {
+		self.0.url.as_deref()
+	}
+
+	/// The URL to the author's avatar.
+	async fn avatar(&self) -> Option<&str> {
+		self.0.avatar.as_deref()
+	}
+}
+
+/// A tag, along with the number of public articles having it.
+#[derive(SimpleObject)]
+pub struct Tag {
+	/// The tag's name.
+	name: String,
+	/// The number of public articles having the tag.
+	count: usize,
+}
@@ -0,0 +1,128 @@
+//! This module translates the user interface of the blog.
+//!
+//! Translations are written in the [Fluent](https://projectfluent.org/) format, in the `locales`
+//! directory.
+
+use anyhow::{anyhow, Result};
+use axum::http::{header::ACCEPT_LANGUAGE, HeaderMap};
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/// Available locales, along with their translations.
+const LOCALES: &[(&str, &str)] = &[
+	("en", include_str!("../../locales/en.ftl")),
+	("fr", include_str!("../../locales/fr.ftl")),
+];
+
+/// Translations of the user interface in all available locales.
+pub struct Translations {
+	/// The translations of each locale.
+	bundles: Vec<FluentBundle<FluentResource>>,
+	/// The index of the locale used when the client does not accept any available locale.
+	default: usize,
+}
+
+impl Translations {
+	/// Loads translations, using `default_locale` when the client does not accept any available
+	/// locale.
+	pub fn new(default_locale: &str) -> Result<Self> {
+		let bundles = LOCALES
+			.iter()
+			.map(|(lang, src)| {
+				let lang: LanguageIdentifier = lang.parse()?;
+				let res = FluentResource::try_new(src.to_string())
+					.map_err(|_| anyhow!("invalid translations for locale `{lang}`"))?;
+				let mut bundle = FluentBundle::new_concurrent(vec![lang]);
+				// Isolation characters are not needed in HTML
+				bundle.set_use_isolating(false);
+				bundle
+					.add_resource(res)
+					.map_err(|_| anyhow!("duplicate translations"))?;
+				Ok(bundle)
+			})
+			.collect::<Result<Vec<_>>>()?;
+		let default = LOCALES
+			.iter()
+			.position(|(lang, _)| *lang == default_locale)
+			.ok_or_else(|| anyhow!("unknown locale `{default_locale}`"))?;
+		Ok(Self { bundles, default })
+	}
+
+	/// Returns the locale that fits best the `Accept-Language` header of a request.
+	pub fn negotiate(&self, headers: &HeaderMap) -> Locale<'_> {
+		let mut accepted: Vec<(&str, f32)> = headers
+			.get(ACCEPT_LANGUAGE)
+			.and_then(|h| h.to_str().ok())
+			.unwrap_or_default()
+			.split(',')
+			.filter_map(|entry| {
+				let mut parts = entry.split(';');
+				let lang = parts.next()?.trim();
+				let quality = parts
+					.find_map(|p| p.trim().strip_prefix("q="))
+					.and_then(|q| q.parse().ok())
+					.unwrap_or(1.0);
+				Some((lang, quality))
+			})
+			.collect();
+		accepted.sort_by(|(_, q1), (_, q2)| q2.total_cmp(q1));
+		let index = accepted.iter().find_map(|(lang, _)| {
+			let primary = lang.split('-').next()?;
+			LOCALES
+				.iter()
+				.position(|(l, _)| l.eq_ignore_ascii_case(primary))
+		});
+		self.get(index.unwrap_or(self.default))
+	}
+
+	/// Returns the locale at the given index.
+	fn get(&self, index: usize) -> Locale<'_> {
+		Locale {
+			bundle: &self.bundles[index],
+			fallback: &self.bundles[self.default],
+			lang: LOCALES[index].0,
+		}
+	}
+}
+
+/// The locale in which a page is rendered.
+#[derive(Clone, Copy)]
+pub struct Locale<'a> {
+	/// The translations of the locale.
+	bundle: &'a FluentBundle<FluentResource>,
+	/// The translations used for messages missing in the locale.
+	fallback: &'a FluentBundle<FluentResource>,
+	/// The language identifier of the locale.
+	lang: &'a str,
+}
+
+impl Locale<'_> {
+	/// Returns the language identifier of the locale.
+	pub fn lang(&self) -> &str {
+		self.lang
+	}
+
+	/// Returns the message with the given `id`.
+	pub fn get(&self, id: &str) -> String {
+		self.get_with(id, &[])
+	}
+
+	/// Returns the message with the given `id`, with the given arguments.
+	///
+	/// If the message does not exist, its identifier is returned.
+	pub fn get_with(&self, id: &str, args: &[(&str, &str)]) -> String {
+		let mut fluent_args = FluentArgs::new();
+		for (name, value) in args {
+			fluent_args.set(*name, *value);
+		}
+		[self.bundle, self.fallback]
+			.into_iter()
+			.find_map(|bundle| {
+				let pattern = bundle.get_message(id)?.value()?;
+				let mut errors = vec![];
+				let msg = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+				Some(msg.into_owned())
+			})
+			.unwrap_or_else(|| id.to_owned())
+	}
+}
@@ -0,0 +1,100 @@
+//! This module resizes and transcodes article images, keeping generated variants in a disk cache.
+
+use anyhow::{bail, Result};
+use image::{imageops::FilterType, ImageFormat, ImageReader};
+use serde::Deserialize;
+use std::{
+	fs,
+	hash::{DefaultHasher, Hash, Hasher},
+	io::Cursor,
+	path::Path,
+	sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The widths images can be resized to. Restricting them bounds the size of the cache.
+pub const ALLOWED_WIDTHS: &[u32] = &[160, 320, 480, 640, 800, 1024, 1280, 1600, 1920];
+
+/// An output image format.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+	Jpeg,
+	Png,
+	Webp,
+}
+
+impl Format {
+	/// Returns the corresponding format of the `image` crate.
+	fn image_format(self) -> ImageFormat {
+		match self {
+			Self::Jpeg => ImageFormat::Jpeg,
+			Self::Png => ImageFormat::Png,
+			Self::Webp => ImageFormat::WebP,
+		}
+	}
+
+	/// Returns the MIME type of the format.
+	pub fn mime(self) -> &'static str {
+		match self {
+			Self::Jpeg => "image/jpeg",
+			Self::Png => "image/png",
+			Self::Webp => "image/webp",
+		}
+	}
+
+	/// Returns the file extension of the format.
+	fn extension(self) -> &'static str {
+		match self {
+			Self::Jpeg => "jpg",
+			Self::Png => "png",
+			Self::Webp => "webp",
+		}
+	}
+}
+
+/// Returns the variant of the image at `source`, at most `width` pixels wide and in the given
+/// `format`, along with its format.
+///
+/// If the format is not specified, the format of the source is kept when possible.
+///
+/// Generated variants are stored in `cache_dir` and reused as long as the source is not modified.
+pub fn get_variant(
+	source: &Path,
+	cache_dir: &Path,
+	width: Option<u32>,
+	format: Option<Format>,
+) -> Result<(Vec<u8>, Format)> {
+	let modified = fs::metadata(source)?.modified()?;
+	let reader = ImageReader::open(source)?.with_guessed_format()?;
+	let format = format.unwrap_or(match reader.format() {
+		Some(ImageFormat::Jpeg) => Format::Jpeg,
+		Some(ImageFormat::WebP) => Format::Webp,
+		_ => Format::Png,
+	});
+	let mut hasher = DefaultHasher::new();
+	(source, modified, width, format).hash(&mut hasher);
+	let cache_path = cache_dir.join(format!("{:016x}.{}", hasher.finish(), format.extension()));
+	if let Ok(data) = fs::read(&cache_path) {
+		return Ok((data, format));
+	}
+	let Some(_) = reader.format() else {
+		bail!("unsupported image format");
+	};
+	let mut image = reader.decode()?;
+	if let Some(width) = width.filter(|w| *w < image.width()) {
+		image = image.resize(width, u32::MAX, FilterType::Lanczos3);
+	}
+	// JPEG does not support transparency
+	if format == Format::Jpeg {
+		image = image.to_rgb8().into();
+	}
+	let mut data = vec![];
+	image.write_to(&mut Cursor::new(&mut data), format.image_format())?;
+	// Write to a temporary file first so that concurrent requests never read a partial variant
+	static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+	let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+	let tmp_path = cache_path.with_extension(format!("{n}.tmp"));
+	fs::write(&tmp_path, &data)?;
+	fs::rename(tmp_path, cache_path)?;
+	Ok((data, format))
+}
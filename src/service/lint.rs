@@ -0,0 +1,146 @@
+//! This module implements consistency checks over articles.
+
+use crate::{config::Config, service::article::Article, util::list_files};
+use anyhow::Result;
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::{
+	fmt,
+	fmt::{Display, Formatter},
+	io,
+	path::{Path, PathBuf},
+};
+
+/// The maximum length of a title, in characters, before it gets truncated by search engines.
+const TITLE_MAX_LEN: usize = 70;
+
+/// An issue found on an article.
+pub enum Issue {
+	/// The article has no description.
+	MissingDescription,
+	/// The article's title is too long.
+	TitleTooLong(usize),
+	/// An image has no alternative text.
+	MissingAltText(String),
+	/// A heading skips levels relative to the previous one.
+	HeadingLevelJump {
+		/// The previous heading level.
+		from: HeadingLevel,
+		/// The level of the offending heading.
+		to: HeadingLevel,
+	},
+}
+
+impl Display for Issue {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::MissingDescription => write!(f, "missing description"),
+			Self::TitleTooLong(len) => {
+				write!(f, "title is too long ({len} > {TITLE_MAX_LEN} characters)")
+			}
+			Self::MissingAltText(url) => write!(f, "missing alt text on image `{url}`"),
+			Self::HeadingLevelJump { from, to } => {
+				write!(f, "heading level jumps from {from} to {to}")
+			}
+		}
+	}
+}
+
+/// Checks the given article and returns the list of issues.
+pub fn check_article(article: &Article) -> Vec<Issue> {
+	let mut issues = vec![];
+	if article.description.trim().is_empty() {
+		issues.push(Issue::MissingDescription);
+	}
+	let title_len = article.title.chars().count();
+	if title_len > TITLE_MAX_LEN {
+		issues.push(Issue::TitleTooLong(title_len));
+	}
+	let mut last_heading = None;
+	// The URL of the image being read, along with whether it has alternative text
+	let mut image: Option<(String, bool)> = None;
+	for event in Parser::new_ext(&article.markdown, Options::all()) {
+		match event {
+			Event::Start(Tag::Heading { level, .. }) => {
+				if let Some(from) = last_heading {
+					if level as usize > from as usize + 1 {
+						issues.push(Issue::HeadingLevelJump { from, to: level });
+					}
+				}
+				last_heading = Some(level);
+			}
+			Event::Start(Tag::Image { dest_url, .. }) => {
+				image = Some((dest_url.into_string(), false))
+			}
+			Event::Text(text) | Event::Code(text) if !text.trim().is_empty() => {
+				if let Some((_, alt)) = &mut image {
+					*alt = true;
+				}
+			}
+			Event::End(TagEnd::Image) => {
+				if let Some((url, false)) = image.take() {
+					issues.push(Issue::MissingAltText(url));
+				}
+			}
+			_ => {}
+		}
+	}
+	issues
+}
+
+/// Tells whether `text` references the asset at the relative `path`.
+///
+/// The path must not be part of a longer file name, so that `a.png` is not referenced by
+/// `data.png` nor `a.png.bak`.
+fn references(text: &str, path: &str) -> bool {
+	let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.');
+	text.match_indices(path).any(|(i, _)| {
+		let before = text[..i].chars().next_back();
+		let after = text[i + path.len()..].chars().next();
+		!before.is_some_and(is_name_char) && !after.is_some_and(|c| is_name_char(c) || c == '/')
+	})
+}
+
+/// Returns the list of assets that are not referenced by any article.
+pub fn unreferenced_assets(assets_path: &Path, articles: &[Article]) -> io::Result<Vec<PathBuf>> {
+	let mut files = vec![];
+	list_files(assets_path, assets_path, &mut files)?;
+	files.retain(|file| {
+		let file = file.to_string_lossy();
+		!articles
+			.iter()
+			.any(|a| references(&a.cover_url, &file) || references(&a.markdown, &file))
+	});
+	files.sort_unstable();
+	Ok(files)
+}
+
+/// Runs all checks over articles and prints a report.
+///
+/// The function returns `true` if no issue has been found.
+pub fn run(config: &Config) -> Result<bool> {
+	let articles = Article::load_all(config)?;
+	let mut ok = true;
+	for article in &articles {
+		let issues = check_article(article);
+		if issues.is_empty() {
+			continue;
+		}
+		ok = false;
+		println!("{slug}:", slug = article.slug);
+		for issue in issues {
+			println!("\t- {issue}");
+		}
+	}
+	let assets = unreferenced_assets(&config.article_assets_path, &articles)?;
+	if !assets.is_empty() {
+		ok = false;
+		println!("unreferenced assets:");
+		for asset in assets {
+			println!("\t- {}", asset.display());
+		}
+	}
+	if ok {
+		println!("{} articles checked, no issue found", articles.len());
+	}
+	Ok(ok)
+}
@@ -0,0 +1,66 @@
+//! This module tracks the number of visitors currently reading the blog.
+
+use std::{
+	collections::HashMap,
+	hash::{BuildHasher, Hash, Hasher, RandomState},
+	net::IpAddr,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+/// Counter of distinct visitors over a sliding window of time.
+///
+/// Visitors are identified by a keyed hash of their IP address and user agent, so that the
+/// original values are never stored.
+pub struct LiveVisitors {
+	/// The duration after which a visitor is not considered online anymore.
+	window: Duration,
+	/// The hasher used to identify visitors.
+	hasher: RandomState,
+	/// Hashed visitors, along with the last time they have been seen.
+	visitors: Mutex<HashMap<u64, Instant>>,
+	/// The last time expired visitors have been removed.
+	last_prune: Mutex<Instant>,
+}
+
+impl LiveVisitors {
+	/// Creates a new counter with the given window.
+	pub fn new(window: Duration) -> Self {
+		Self {
+			window,
+			hasher: RandomState::new(),
+			visitors: Default::default(),
+			last_prune: Mutex::new(Instant::now()),
+		}
+	}
+
+	/// Returns the duration after which a visitor is not considered online anymore.
+	pub fn window(&self) -> Duration {
+		self.window
+	}
+
+	/// Records a request from the given visitor.
+	pub fn record(&self, ip: IpAddr, user_agent: &str) {
+		let mut hasher = self.hasher.build_hasher();
+		ip.hash(&mut hasher);
+		user_agent.hash(&mut hasher);
+		let id = hasher.finish();
+		let now = Instant::now();
+		let mut visitors = self.visitors.lock().unwrap();
+		visitors.insert(id, now);
+		// Prune once per window to keep the map bounded
+		let mut last_prune = self.last_prune.lock().unwrap();
+		if now.duration_since(*last_prune) >= self.window {
+			visitors.retain(|_, last| now.duration_since(*last) < self.window);
+			*last_prune = now;
+		}
+	}
+
+	/// Returns the number of distinct visitors seen during the window.
+	pub fn count(&self) -> usize {
+		let now = Instant::now();
+		let mut visitors = self.visitors.lock().unwrap();
+		visitors.retain(|_, last| now.duration_since(*last) < self.window);
+		visitors.len()
+	}
+}
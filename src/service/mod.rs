@@ -1 +1,21 @@
 pub mod article;
+pub mod editor;
+pub mod feed;
+pub mod files;
+pub mod graphql;
+pub mod i18n;
+pub mod image;
+pub mod lint;
+pub mod live;
+pub mod not_found;
+pub mod og;
+pub mod outbound;
+pub mod page_cache;
+pub mod persist;
+pub mod publication;
+pub mod rate_limit;
+pub mod reaction;
+pub mod report;
+pub mod search;
+pub mod webhook;
+pub mod webmention;
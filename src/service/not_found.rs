@@ -0,0 +1,73 @@
+//! This module records requests to pages that do not exist, to discover broken inbound links.
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use serde::Serialize;
+use std::{collections::HashMap, num::NonZeroUsize, sync::Mutex};
+use utoipa::ToSchema;
+
+/// The maximum number of distinct URIs recorded. Beyond that, the least recently requested URIs
+/// are forgotten.
+const MAX_ENTRIES: NonZeroUsize = NonZeroUsize::new(1000).unwrap();
+/// The maximum number of distinct referrers recorded per URI.
+const MAX_REFERRERS: usize = 20;
+
+/// Statistics about a URI that has been requested but does not exist.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct NotFoundEntry {
+	/// The requested URI.
+	pub uri: String,
+	/// The number of times the URI has been requested.
+	pub count: u64,
+	/// The last time the URI has been requested.
+	pub last_seen: DateTime<Utc>,
+	/// The pages linking to the URI, along with the number of requests coming from each.
+	pub referrers: HashMap<String, u64>,
+}
+
+/// A log of requests to pages that do not exist.
+pub struct NotFoundLog {
+	/// Entries, by URI.
+	entries: Mutex<LruCache<String, NotFoundEntry>>,
+}
+
+impl Default for NotFoundLog {
+	fn default() -> Self {
+		Self {
+			entries: Mutex::new(LruCache::new(MAX_ENTRIES)),
+		}
+	}
+}
+
+impl NotFoundLog {
+	/// Records a request to the given `uri` that does not exist, with the optional referrer.
+	pub fn record(&self, uri: &str, referrer: Option<&str>) {
+		let mut entries = self.entries.lock().unwrap();
+		let entry = entries.get_or_insert_mut(uri.to_owned(), || NotFoundEntry {
+			uri: uri.to_owned(),
+			count: 0,
+			last_seen: Utc::now(),
+			referrers: HashMap::new(),
+		});
+		entry.count += 1;
+		entry.last_seen = Utc::now();
+		if let Some(referrer) = referrer {
+			if entry.referrers.contains_key(referrer) || entry.referrers.len() < MAX_REFERRERS {
+				*entry.referrers.entry(referrer.to_owned()).or_default() += 1;
+			}
+		}
+	}
+
+	/// Returns all entries, sorted by decreasing number of requests.
+	pub fn report(&self) -> Vec<NotFoundEntry> {
+		let mut entries: Vec<_> = self
+			.entries
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(_, e)| e.clone())
+			.collect();
+		entries.sort_unstable_by(|e1, e2| e1.count.cmp(&e2.count).reverse());
+		entries
+	}
+}
@@ -0,0 +1,79 @@
+//! This module generates Open Graph preview images for articles without a cover.
+
+use crate::service::article::Article;
+use anyhow::{anyhow, Result};
+use html_escape::encode_text;
+use resvg::{tiny_skia, usvg};
+
+/// The width of generated images, in pixels.
+const WIDTH: u32 = 1200;
+/// The height of generated images, in pixels.
+const HEIGHT: u32 = 630;
+/// The maximum number of characters on a line of the title.
+const LINE_LEN: usize = 30;
+/// The maximum number of lines of the title.
+const MAX_LINES: usize = 4;
+
+/// The font used to render text.
+const FONT: &[u8] = include_bytes!("../../assets/font/SourceSansPro.ttf");
+
+/// Splits the given title into lines that fit in the image.
+fn wrap_title(title: &str) -> Vec<String> {
+	let mut lines: Vec<String> = vec![];
+	for word in title.split_whitespace() {
+		match lines.last_mut() {
+			Some(line) if line.chars().count() + 1 + word.chars().count() <= LINE_LEN => {
+				line.push(' ');
+				line.push_str(word);
+			}
+			_ => lines.push(word.to_owned()),
+		}
+	}
+	if lines.len() > MAX_LINES {
+		lines.truncate(MAX_LINES);
+		if let Some(line) = lines.last_mut() {
+			line.push('…');
+		}
+	}
+	lines
+}
+
+/// Returns the SVG document of the preview image for the given article, on the blog at `domain`.
+fn get_svg(article: &Article, domain: &str) -> String {
+	let title: String = wrap_title(&article.title)
+		.iter()
+		.enumerate()
+		.map(|(i, line)| {
+			format!(
+				r#"<tspan x="80" dy="{dy}">{line}</tspan>"#,
+				dy = if i == 0 { 0 } else { 80 },
+				line = encode_text(line)
+			)
+		})
+		.collect();
+	let domain = encode_text(domain);
+	format!(
+		r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}">
+	<rect width="100%" height="100%" fill="#0f0f0f"/>
+	<circle cx="1100" cy="80" r="260" fill="#1abc9c" fill-opacity="0.25"/>
+	<circle cx="120" cy="620" r="200" fill="#1abc9c" fill-opacity="0.15"/>
+	<text x="80" y="200" font-size="72" fill="#ffffff">{title}</text>
+	<text x="80" y="560" font-size="36" fill="#1abc9c">{domain}</text>
+</svg>"##
+	)
+}
+
+/// Renders the preview image for the given article, on the blog at `domain`, in PNG format.
+pub fn generate(article: &Article, domain: &str) -> Result<Vec<u8>> {
+	let mut opt = usvg::Options::default();
+	opt.fontdb_mut().load_font_data(FONT.to_vec());
+	// Use the embedded font by default
+	if let Some((family, _)) = opt.fontdb.faces().find_map(|f| f.families.first()) {
+		opt.font_family = family.clone();
+	}
+	let tree = usvg::Tree::from_str(&get_svg(article, domain), &opt)?;
+	let mut pixmap =
+		tiny_skia::Pixmap::new(WIDTH, HEIGHT).ok_or_else(|| anyhow!("invalid image size"))?;
+	resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+	Ok(pixmap.encode_png()?)
+}
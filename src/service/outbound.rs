@@ -0,0 +1,180 @@
+//! This module tracks clicks on links leading outside of the blog.
+
+use crate::config::Config;
+use axum::http::Uri;
+use serde::Serialize;
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Mutex,
+};
+use utoipa::ToSchema;
+
+/// Returns the domain of the given absolute URL, if any.
+pub fn get_domain(url: &str) -> Option<String> {
+	let uri: Uri = url.parse().ok()?;
+	if !matches!(uri.scheme_str(), Some("http" | "https")) {
+		return None;
+	}
+	Some(uri.host()?.to_ascii_lowercase())
+}
+
+/// Tells whether `domain` matches any of the given domains, including their subdomains.
+fn domain_in(domain: &str, list: &[String]) -> bool {
+	list.iter().any(|d| {
+		domain == d
+			|| domain
+				.strip_suffix(d.as_str())
+				.is_some_and(|prefix| prefix.ends_with('.'))
+	})
+}
+
+/// The number of clicks towards a domain from an article.
+#[derive(Serialize, ToSchema)]
+pub struct OutboundEntry {
+	/// The slug of the article on which the link has been clicked, if known.
+	pub article: Option<String>,
+	/// The domain the link leads to.
+	pub domain: String,
+	/// The number of clicks.
+	pub count: u64,
+}
+
+/// Tracker of outbound clicks.
+pub struct OutboundTracker {
+	/// Tells whether clicks are tracked.
+	enabled: bool,
+	/// Domains that can always be redirected to, even if they are not linked in an article.
+	allowlist: Vec<String>,
+	/// Domains that are never tracked nor redirected to.
+	denylist: Vec<String>,
+	/// The URLs of the tracked links of each article, by slug.
+	links: Mutex<HashMap<String, HashSet<String>>>,
+	/// Number of clicks, by article slug and target domain.
+	clicks: Mutex<HashMap<(Option<String>, String), u64>>,
+}
+
+impl OutboundTracker {
+	/// Creates a new tracker from the given configuration.
+	pub fn new(config: &Config) -> Self {
+		Self {
+			enabled: config.outbound_tracking,
+			allowlist: config.outbound_allowlist.clone(),
+			denylist: config.outbound_denylist.clone(),
+			links: Default::default(),
+			clicks: Default::default(),
+		}
+	}
+
+	/// Tells whether links to the given URL in articles shall go through the tracker.
+	pub fn is_tracked(&self, url: &str) -> bool {
+		self.enabled && get_domain(url).is_some_and(|d| !domain_in(&d, &self.denylist))
+	}
+
+	/// Sets the URLs of the tracked links of the article with the given slug.
+	pub fn set_links(&self, slug: &str, links: HashSet<String>) {
+		self.links.lock().unwrap().insert(slug.to_owned(), links);
+	}
+
+	/// Tells whether the tracker may redirect to `url` for a click from the article `from`.
+	///
+	/// The URL must be exactly the target of a link of the article, or be on an allowed domain.
+	/// This prevents the tracker from being used as an open redirect.
+	pub fn can_redirect(&self, from: Option<&str>, url: &str) -> bool {
+		let Some(domain) = get_domain(url) else {
+			return false;
+		};
+		if domain_in(&domain, &self.denylist) {
+			return false;
+		}
+		let linked = from.is_some_and(|slug| {
+			self.links
+				.lock()
+				.unwrap()
+				.get(slug)
+				.is_some_and(|links| links.contains(url))
+		});
+		linked || domain_in(&domain, &self.allowlist)
+	}
+
+	/// Records a click from the given article towards `url`.
+	pub fn record(&self, article: Option<&str>, url: &str) {
+		let Some(domain) = get_domain(url) else {
+			return;
+		};
+		let mut clicks = self.clicks.lock().unwrap();
+		*clicks
+			.entry((article.map(str::to_owned), domain))
+			.or_default() += 1;
+	}
+
+	/// Returns the number of clicks, sorted by decreasing count.
+	pub fn report(&self) -> Vec<OutboundEntry> {
+		let clicks = self.clicks.lock().unwrap();
+		let mut entries: Vec<_> = clicks
+			.iter()
+			.map(|((article, domain), count)| OutboundEntry {
+				article: article.clone(),
+				domain: domain.clone(),
+				count: *count,
+			})
+			.collect();
+		entries.sort_unstable_by(|e1, e2| e1.count.cmp(&e2.count).reverse());
+		entries
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tracker() -> OutboundTracker {
+		let tracker = OutboundTracker {
+			enabled: true,
+			allowlist: vec!["allowed.org".to_owned()],
+			denylist: vec!["denied.org".to_owned()],
+			links: Default::default(),
+			clicks: Default::default(),
+		};
+		let links = [
+			"https://example.com/page",
+			"https://denied.org/page",
+			"javascript:alert(1)",
+		];
+		tracker.set_links("hello", links.into_iter().map(str::to_owned).collect());
+		tracker
+	}
+
+	#[test]
+	fn redirect_linked() {
+		let tracker = tracker();
+		assert!(tracker.can_redirect(Some("hello"), "https://example.com/page"));
+	}
+
+	#[test]
+	fn redirect_unlinked() {
+		let tracker = tracker();
+		// Domain linked by no article
+		assert!(!tracker.can_redirect(Some("hello"), "https://evil.com/"));
+		assert!(!tracker.can_redirect(None, "https://evil.com/"));
+		// Other page on a linked domain
+		assert!(!tracker.can_redirect(Some("hello"), "https://example.com/other"));
+		// Link of another article
+		assert!(!tracker.can_redirect(Some("other"), "https://example.com/page"));
+		assert!(!tracker.can_redirect(None, "https://example.com/page"));
+	}
+
+	#[test]
+	fn redirect_allowlist() {
+		let tracker = tracker();
+		assert!(tracker.can_redirect(None, "https://allowed.org/any"));
+		assert!(tracker.can_redirect(None, "https://sub.allowed.org/any"));
+		assert!(!tracker.can_redirect(None, "https://notallowed.org/any"));
+	}
+
+	#[test]
+	fn redirect_denylist() {
+		let tracker = tracker();
+		assert!(!tracker.can_redirect(Some("hello"), "https://denied.org/page"));
+		assert!(!tracker.can_redirect(Some("hello"), "javascript:alert(1)"));
+	}
+}
@@ -0,0 +1,58 @@
+//! This module caches rendered pages, since they do not change until articles are recompiled.
+
+use crate::util::etag;
+use axum::body::Bytes;
+use lru::LruCache;
+use std::{num::NonZeroUsize, sync::Mutex};
+
+/// A rendered page.
+#[derive(Clone)]
+pub struct CachedPage {
+	/// The HTML content.
+	pub html: Bytes,
+	/// The entity tag of the content.
+	pub etag: String,
+	/// The version of the data the page has been rendered from.
+	version: u64,
+}
+
+/// A cache of rendered pages, evicting the least recently used ones.
+pub struct PageCache {
+	/// Pages, by key.
+	pages: Mutex<LruCache<String, CachedPage>>,
+}
+
+impl PageCache {
+	/// Creates a new cache holding at most `capacity` pages.
+	pub fn new(capacity: usize) -> Self {
+		let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+		Self {
+			pages: Mutex::new(LruCache::new(capacity)),
+		}
+	}
+
+	/// Returns the page with the given `key`, rendering it with `render` if it is not cached.
+	///
+	/// `version` identifies the data the page is rendered from. If it differs from the version of
+	/// the cached page, the page is rendered again.
+	pub fn get_or_render<E>(
+		&self,
+		key: &str,
+		version: u64,
+		render: impl FnOnce() -> Result<String, E>,
+	) -> Result<CachedPage, E> {
+		if let Some(page) = self.pages.lock().unwrap().get(key) {
+			if page.version == version {
+				return Ok(page.clone());
+			}
+		}
+		let html = render()?;
+		let page = CachedPage {
+			etag: etag(html.as_bytes()),
+			html: html.into(),
+			version,
+		};
+		self.pages.lock().unwrap().put(key.to_owned(), page.clone());
+		Ok(page)
+	}
+}
@@ -0,0 +1,66 @@
+//! This module persists data collected while the server runs, so that it survives restarts.
+//!
+//! Each kind of data is stored as a JSON file in the data directory.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+use tracing::{error, warn};
+
+/// A JSON file holding persisted data.
+pub struct JsonFile {
+	/// The path to the file.
+	path: PathBuf,
+	/// Held while the file is written, so that writes do not interleave.
+	lock: Mutex<()>,
+}
+
+impl JsonFile {
+	/// Creates an instance for the file with the given `name` in the directory `dir`.
+	pub fn new(dir: &Path, name: &str) -> Self {
+		Self {
+			path: dir.join(name),
+			lock: Mutex::new(()),
+		}
+	}
+
+	/// Reads the data from the file.
+	///
+	/// If the file does not exist, the function returns `None`. If it cannot be read, the error is
+	/// logged and the function returns `None` too, so that the server can start anyway.
+	pub fn load<T: DeserializeOwned>(&self) -> Option<T> {
+		let content = match fs::read(&self.path) {
+			Ok(content) => content,
+			Err(error) if error.kind() == io::ErrorKind::NotFound => return None,
+			Err(error) => {
+				error!(%error, path = %self.path.display(), "could not read data file");
+				return None;
+			}
+		};
+		serde_json::from_slice(&content)
+			.inspect_err(|error| {
+				error!(%error, path = %self.path.display(), "could not parse data file");
+			})
+			.ok()
+	}
+
+	/// Writes the data returned by `data` to the file.
+	///
+	/// `data` is called while no other write is in progress, so that the file always ends up
+	/// holding the latest data. The file is replaced atomically, so that it is never left
+	/// truncated.
+	///
+	/// Errors are logged, since the data is still available in memory.
+	pub fn save<T: Serialize>(&self, data: impl FnOnce() -> T) {
+		let _guard = self.lock.lock().unwrap();
+		let content = serde_json::to_vec(&data()).unwrap();
+		let tmp_path = self.path.with_extension("json.tmp");
+		let res = fs::write(&tmp_path, content).and_then(|_| fs::rename(&tmp_path, &self.path));
+		if let Err(error) = res {
+			warn!(%error, path = %self.path.display(), "could not write data file");
+		}
+	}
+}
@@ -0,0 +1,53 @@
+//! This module notifies other services when articles are published.
+
+use crate::{
+	service::{article::ArticleJson, persist::JsonFile, webmention},
+	Context,
+};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+/// The interval at which articles are checked for publication.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// The name of the file holding the slugs of the articles that have been notified.
+const PUBLISHED_FILE: &str = "published.json";
+
+/// Watches articles becoming public, sending the `article.published` event to webhooks and
+/// webmentions to the pages they link to.
+///
+/// Notified articles are persisted, so that articles becoming public while the server is stopped,
+/// or deployed while already public, are notified when it starts. When no article has been
+/// recorded yet, articles that are already public are recorded without being notified.
+pub async fn watch(ctx: Arc<Context>) {
+	let file = JsonFile::new(&ctx.data_path, PUBLISHED_FILE);
+	let mut published: HashSet<String> = file.load().unwrap_or_else(|| {
+		let published = ctx
+			.articles
+			.iter()
+			.filter(|(a, _)| a.is_public())
+			.map(|(a, _)| a.slug.clone())
+			.collect();
+		file.save(|| &published);
+		published
+	});
+	let mut interval = tokio::time::interval(CHECK_INTERVAL);
+	loop {
+		interval.tick().await;
+		let mut changed = false;
+		for (article, _) in ctx.articles.iter().filter(|(a, _)| a.is_public()) {
+			if !published.insert(article.slug.clone()) {
+				continue;
+			}
+			if ctx.webhooks.is_enabled() {
+				let json = ArticleJson::new(article, &ctx.base_url, None);
+				ctx.webhooks.send("article.published", &json);
+			}
+			if ctx.webmentions.is_enabled() {
+				tokio::spawn(webmention::send(ctx.clone(), article.slug.clone()));
+			}
+			changed = true;
+		}
+		if changed {
+			file.save(|| &published);
+		}
+	}
+}
@@ -0,0 +1,84 @@
+//! This module implements rate limiting of clients.
+
+use std::{
+	collections::HashMap,
+	net::IpAddr,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+/// The interval at which buckets of idle clients are removed.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The token bucket of a client.
+struct Bucket {
+	/// The number of available tokens.
+	tokens: f64,
+	/// The last time the bucket has been refilled.
+	last_refill: Instant,
+}
+
+/// A token bucket rate limiter, keyed by client IP address.
+pub struct RateLimiter {
+	/// The maximum number of tokens in a bucket, which is the maximum burst of requests.
+	capacity: f64,
+	/// The number of tokens added to each bucket per second.
+	refill_rate: f64,
+	/// Addresses that are never limited.
+	allowlist: Vec<IpAddr>,
+	/// Buckets, by client address.
+	buckets: Mutex<HashMap<IpAddr, Bucket>>,
+	/// The last time buckets of idle clients have been removed.
+	last_prune: Mutex<Instant>,
+}
+
+impl RateLimiter {
+	/// Creates a new rate limiter allowing bursts of `burst` requests, then `per_minute`
+	/// requests per minute.
+	pub fn new(burst: u32, per_minute: u32, allowlist: Vec<IpAddr>) -> Self {
+		Self {
+			capacity: burst.max(1) as f64,
+			refill_rate: per_minute.max(1) as f64 / 60.,
+			allowlist,
+			buckets: Default::default(),
+			last_prune: Mutex::new(Instant::now()),
+		}
+	}
+
+	/// Consumes a token for a request from the given client.
+	///
+	/// If the client has exceeded its rate, the function returns the duration to wait before
+	/// the next request is accepted.
+	pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+		if self.allowlist.contains(&ip) {
+			return Ok(());
+		}
+		let now = Instant::now();
+		let mut buckets = self.buckets.lock().unwrap();
+		let bucket = buckets.entry(ip).or_insert(Bucket {
+			tokens: self.capacity,
+			last_refill: now,
+		});
+		let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+		bucket.tokens = (bucket.tokens + elapsed * self.refill_rate).min(self.capacity);
+		bucket.last_refill = now;
+		let res = if bucket.tokens >= 1. {
+			bucket.tokens -= 1.;
+			Ok(())
+		} else {
+			Err(Duration::from_secs_f64(
+				(1. - bucket.tokens) / self.refill_rate,
+			))
+		};
+		// Remove buckets that would be full by now, since they are equivalent to new ones
+		let mut last_prune = self.last_prune.lock().unwrap();
+		if now.duration_since(*last_prune) >= PRUNE_INTERVAL {
+			buckets.retain(|_, b| {
+				let elapsed = now.duration_since(b.last_refill).as_secs_f64();
+				b.tokens + elapsed * self.refill_rate < self.capacity
+			});
+			*last_prune = now;
+		}
+		res
+	}
+}
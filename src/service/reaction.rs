@@ -0,0 +1,176 @@
+//! This module implements anonymous reactions on articles.
+//!
+//! Since visitors are not identified, a reaction is deduplicated using a hash of the client's
+//! address, so that each address can give each reaction once per article during a time window.
+//!
+//! The number of reactions is persisted so that it survives restarts, while the addresses used
+//! for deduplication are kept in memory only.
+
+use crate::service::persist::JsonFile;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::HashMap,
+	hash::{BuildHasher, RandomState},
+	net::IpAddr,
+	path::Path,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+/// The duration during which an address cannot give the same reaction to an article again.
+const DEDUP_WINDOW: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// The maximum number of addresses remembered per article. Beyond that, the oldest are
+/// forgotten.
+const MAX_CLIENTS: usize = 10_000;
+/// The name of the file holding the number of reactions.
+const REACTIONS_FILE: &str = "reactions.json";
+
+/// A reaction to an article.
+#[derive(Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Reaction {
+	Heart,
+	ThumbsUp,
+}
+
+impl Reaction {
+	/// All reactions, in display order.
+	pub const ALL: [Self; 2] = [Self::Heart, Self::ThumbsUp];
+
+	/// Returns the name of the reaction, as given in forms.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Heart => "heart",
+			Self::ThumbsUp => "thumbs_up",
+		}
+	}
+
+	/// Returns the emoji representing the reaction.
+	pub fn emoji(self) -> &'static str {
+		match self {
+			Self::Heart => "❤️",
+			Self::ThumbsUp => "👍",
+		}
+	}
+}
+
+/// The reactions to an article.
+#[derive(Default)]
+struct ArticleReactions {
+	/// The number of each reaction, in the order of [`Reaction::ALL`].
+	counts: [u64; Reaction::ALL.len()],
+	/// The hashed addresses which have reacted, along with their reaction and the time at which
+	/// they have reacted.
+	clients: HashMap<(u64, Reaction), Instant>,
+	/// The last time a reaction has been added since the server started, if any.
+	modified: Option<DateTime<Utc>>,
+}
+
+/// Reactions to articles.
+pub struct Reactions {
+	/// The hasher used to identify clients.
+	hasher: RandomState,
+	/// Reactions, by article slug.
+	articles: Mutex<HashMap<String, ArticleReactions>>,
+	/// Incremented each time a reaction is added.
+	revision: AtomicU64,
+	/// The file in which the number of reactions is persisted.
+	file: JsonFile,
+}
+
+impl Reactions {
+	/// Creates a new instance, loading the number of reactions persisted in the directory
+	/// `data_path`.
+	pub fn new(data_path: &Path) -> Self {
+		let file = JsonFile::new(data_path, REACTIONS_FILE);
+		let counts: HashMap<String, HashMap<Reaction, u64>> = file.load().unwrap_or_default();
+		let articles = counts
+			.into_iter()
+			.map(|(slug, counts)| {
+				let article = ArticleReactions {
+					counts: Reaction::ALL.map(|r| counts.get(&r).copied().unwrap_or(0)),
+					clients: HashMap::new(),
+					modified: None,
+				};
+				(slug, article)
+			})
+			.collect();
+		Self {
+			hasher: RandomState::new(),
+			articles: Mutex::new(articles),
+			revision: AtomicU64::new(0),
+			file,
+		}
+	}
+
+	/// Returns the number of each reaction, by article slug.
+	fn counts(&self) -> HashMap<String, HashMap<Reaction, u64>> {
+		let articles = self.articles.lock().unwrap();
+		articles
+			.iter()
+			.map(|(slug, article)| {
+				let counts = Reaction::ALL.into_iter().zip(article.counts).collect();
+				(slug.clone(), counts)
+			})
+			.collect()
+	}
+
+	/// Adds a reaction from the given client to the article with the given slug.
+	///
+	/// If the client has already given the same reaction recently, the function returns `false`.
+	pub fn add(&self, slug: &str, reaction: Reaction, ip: IpAddr) -> bool {
+		let client = self.hasher.hash_one(ip);
+		let now = Instant::now();
+		{
+			let mut articles = self.articles.lock().unwrap();
+			let article = articles.entry(slug.to_owned()).or_default();
+			article
+				.clients
+				.retain(|_, date| now.duration_since(*date) < DEDUP_WINDOW);
+			if article.clients.contains_key(&(client, reaction)) {
+				return false;
+			}
+			if article.clients.len() >= MAX_CLIENTS {
+				let oldest = article
+					.clients
+					.iter()
+					.min_by_key(|(_, date)| **date)
+					.map(|(key, _)| *key);
+				if let Some(oldest) = oldest {
+					article.clients.remove(&oldest);
+				}
+			}
+			article.clients.insert((client, reaction), now);
+			let i = Reaction::ALL.iter().position(|r| *r == reaction).unwrap();
+			article.counts[i] += 1;
+			article.modified = Some(Utc::now());
+		}
+		self.revision.fetch_add(1, Ordering::Relaxed);
+		self.file.save(|| self.counts());
+		true
+	}
+
+	/// Returns the current revision, along with the number of each reaction to the article with
+	/// the given slug, in the order of [`Reaction::ALL`].
+	pub fn get(&self, slug: &str) -> (u64, [u64; Reaction::ALL.len()]) {
+		let articles = self.articles.lock().unwrap();
+		let counts = articles.get(slug).map(|a| a.counts).unwrap_or_default();
+		(self.revision.load(Ordering::Relaxed), counts)
+	}
+
+	/// Returns the last time a reaction has been added to the article with the given slug since the
+	/// server started, if any.
+	pub fn last_modified(&self, slug: &str) -> Option<DateTime<Utc>> {
+		let articles = self.articles.lock().unwrap();
+		articles.get(slug).and_then(|a| a.modified)
+	}
+
+	/// Returns the total number of reactions to the article with the given slug.
+	pub fn total(&self, slug: &str) -> u64 {
+		self.get(slug).1.iter().sum()
+	}
+}
@@ -0,0 +1,64 @@
+//! This module reports server errors to an external webhook, so that they do not only live in
+//! logs.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::warn;
+
+/// The timeout for requests to the webhook.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A server error, sent to the webhook as JSON.
+#[derive(Serialize)]
+pub struct ErrorReport {
+	/// The date at which the error occurred.
+	pub date: DateTime<Utc>,
+	/// The HTTP status of the response.
+	pub status: u16,
+	/// The method of the request.
+	pub method: String,
+	/// The route that has been matched, or the path if none matched.
+	pub route: String,
+	/// The identifier of the request.
+	pub request_id: Option<String>,
+	/// Tells whether the request was authenticated as the administrator.
+	pub admin: bool,
+	/// The panic message, if the error is the result of a panic.
+	pub panic: Option<String>,
+}
+
+/// Sends error reports to a webhook.
+pub struct ErrorReporter {
+	/// The HTTP client.
+	client: reqwest::Client,
+	/// The URL of the webhook. If `None`, reports are discarded.
+	webhook: Option<String>,
+}
+
+impl ErrorReporter {
+	/// Creates a new instance sending reports to the given `webhook` with `client`.
+	pub fn new(client: reqwest::Client, webhook: Option<String>) -> Self {
+		Self { client, webhook }
+	}
+
+	/// Sends the given report in the background.
+	pub fn report(&self, report: ErrorReport) {
+		let Some(url) = self.webhook.clone() else {
+			return;
+		};
+		let client = self.client.clone();
+		tokio::spawn(async move {
+			let res = client
+				.post(url)
+				.timeout(WEBHOOK_TIMEOUT)
+				.json(&report)
+				.send()
+				.await
+				.and_then(|r| r.error_for_status());
+			if let Err(error) = res {
+				warn!(%error, "could not send error report");
+			}
+		});
+	}
+}
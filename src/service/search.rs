@@ -0,0 +1,141 @@
+//! This module implements full-text search on articles, ranked by relevance.
+
+use crate::service::article::Article;
+use pulldown_cmark::{Event, Parser, TagEnd};
+use tantivy::{
+	collector::TopDocs,
+	doc,
+	query::QueryParser,
+	schema::{
+		Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, STORED, STRING,
+	},
+	snippet::SnippetGenerator,
+	Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument,
+};
+
+/// The tokenizer used for text fields, which handles English word variations.
+const TOKENIZER: &str = "en_stem";
+/// The memory budget of the index writer, in bytes.
+const WRITER_MEMORY: usize = 15_000_000;
+/// The maximum length of a snippet, in characters.
+const SNIPPET_MAX_CHARS: usize = 200;
+
+/// Returns the plain text of the given Markdown.
+fn get_text(markdown: &str) -> String {
+	let mut text = String::new();
+	for event in Parser::new(markdown) {
+		match event {
+			Event::Text(s) | Event::Code(s) => text.push_str(&s),
+			Event::SoftBreak
+			| Event::HardBreak
+			| Event::End(
+				TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::CodeBlock,
+			) => text.push('\n'),
+			_ => {}
+		}
+	}
+	text
+}
+
+/// A search result.
+pub struct SearchHit {
+	/// The slug of the matching article.
+	pub slug: String,
+	/// An excerpt of the article's content, in HTML, with the matching terms highlighted.
+	pub snippet: String,
+}
+
+/// Full-text index of articles.
+pub struct SearchIndex {
+	/// The index.
+	index: Index,
+	/// The reader of the index.
+	reader: IndexReader,
+	/// The field holding the article's slug.
+	slug: Field,
+	/// The field holding the article's title.
+	title: Field,
+	/// The field holding the article's description.
+	description: Field,
+	/// The field holding the article's tags.
+	tags: Field,
+	/// The field holding the article's content, as plain text.
+	content: Field,
+}
+
+impl SearchIndex {
+	/// Builds the index of the given articles.
+	pub fn new<'a>(articles: impl Iterator<Item = &'a Article>) -> tantivy::Result<Self> {
+		let text = TextOptions::default().set_indexing_options(
+			TextFieldIndexing::default()
+				.set_tokenizer(TOKENIZER)
+				.set_index_option(IndexRecordOption::WithFreqsAndPositions),
+		);
+		let mut builder = Schema::builder();
+		let slug = builder.add_text_field("slug", STRING | STORED);
+		let title = builder.add_text_field("title", text.clone());
+		let description = builder.add_text_field("description", text.clone());
+		let tags = builder.add_text_field("tags", text.clone());
+		let content = builder.add_text_field("content", text | STORED);
+		let index = Index::create_in_ram(builder.build());
+		let mut writer: IndexWriter = index.writer_with_num_threads(1, WRITER_MEMORY)?;
+		for article in articles {
+			let mut document = doc!(
+				slug => article.slug.as_str(),
+				title => article.title.as_str(),
+				description => article.description.as_str(),
+				content => get_text(&article.markdown),
+			);
+			for tag in &article.tags {
+				document.add_text(tags, tag);
+			}
+			writer.add_document(document)?;
+		}
+		writer.commit()?;
+		let reader = index
+			.reader_builder()
+			.reload_policy(ReloadPolicy::Manual)
+			.try_into()?;
+		Ok(Self {
+			index,
+			reader,
+			slug,
+			title,
+			description,
+			tags,
+			content,
+		})
+	}
+
+	/// Returns the articles matching the query `q`, from the most to the least relevant.
+	///
+	/// Titles weigh more than tags, which weigh more than descriptions and contents.
+	pub fn search(&self, q: &str) -> tantivy::Result<Vec<SearchHit>> {
+		let searcher = self.reader.searcher();
+		let mut parser = QueryParser::for_index(
+			&self.index,
+			vec![self.title, self.description, self.tags, self.content],
+		);
+		parser.set_field_boost(self.title, 3.0);
+		parser.set_field_boost(self.tags, 2.0);
+		parser.set_field_boost(self.description, 1.5);
+		// Syntax errors are ignored since queries are typed by visitors
+		let (query, _) = parser.parse_query_lenient(q);
+		let limit = searcher.num_docs().max(1) as usize;
+		let top = searcher.search(&query, &TopDocs::with_limit(limit))?;
+		let mut snippets = SnippetGenerator::create(&searcher, &query, self.content)?;
+		snippets.set_max_num_chars(SNIPPET_MAX_CHARS);
+		top.into_iter()
+			.map(|(_, address)| {
+				let document: TantivyDocument = searcher.doc(address)?;
+				let slug = document
+					.get_first(self.slug)
+					.and_then(|v| v.as_str())
+					.unwrap_or_default()
+					.to_owned();
+				let snippet = snippets.snippet_from_doc(&document).to_html();
+				Ok(SearchHit { slug, snippet })
+			})
+			.collect()
+	}
+}
@@ -0,0 +1,188 @@
+//! This module notifies external services (chat rooms, CI, ...) of events on the blog through
+//! webhooks.
+//!
+//! Each event is sent as a JSON `POST` request to every configured URL. The body is signed with
+//! HMAC-SHA256 using the webhook secret, and the signature is given in the `X-Blog-Signature`
+//! header as `sha256=<hex digest>`. Failed deliveries are retried with an exponential backoff.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::json;
+use sha2::Sha256;
+use std::{
+	collections::VecDeque,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+	time::Duration,
+};
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// The timeout for requests to webhooks.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// The maximum number of attempts to deliver an event.
+const MAX_ATTEMPTS: u32 = 5;
+/// The delay before the first retry. It doubles after each attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(10);
+/// The maximum number of deliveries kept in the log.
+const MAX_LOG_ENTRIES: usize = 200;
+
+/// The header holding the name of the event.
+const EVENT_HEADER: &str = "x-blog-event";
+/// The header holding the identifier of the delivery.
+const DELIVERY_HEADER: &str = "x-blog-delivery";
+/// The header holding the signature of the body.
+const SIGNATURE_HEADER: &str = "x-blog-signature";
+
+/// The state of the delivery of an event to a webhook.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Delivery {
+	/// The identifier of the delivery.
+	pub id: u64,
+	/// The name of the event.
+	pub event: &'static str,
+	/// The URL of the webhook.
+	pub url: String,
+	/// The date at which the event occurred.
+	pub date: DateTime<Utc>,
+	/// The number of attempts so far.
+	pub attempts: u32,
+	/// The HTTP status of the last response, if any.
+	pub status: Option<u16>,
+	/// The error of the last attempt, if any.
+	pub error: Option<String>,
+	/// Tells whether the event has been delivered.
+	pub delivered: bool,
+}
+
+/// Sends events to webhooks.
+pub struct Webhooks {
+	/// The HTTP client.
+	client: reqwest::Client,
+	/// The URLs of the webhooks.
+	urls: Vec<String>,
+	/// The secret used to sign requests.
+	secret: String,
+	/// The counter used to identify deliveries.
+	next_id: AtomicU64,
+	/// The log of the latest deliveries, from the oldest to the newest.
+	log: Arc<Mutex<VecDeque<Delivery>>>,
+}
+
+impl Webhooks {
+	/// Creates a new instance sending events to `urls` with `client`, signed with `secret`.
+	pub fn new(client: reqwest::Client, urls: Vec<String>, secret: Option<String>) -> Self {
+		Self {
+			client,
+			urls,
+			secret: secret.unwrap_or_default(),
+			next_id: AtomicU64::new(0),
+			log: Default::default(),
+		}
+	}
+
+	/// Tells whether at least one webhook is configured.
+	pub fn is_enabled(&self) -> bool {
+		!self.urls.is_empty()
+	}
+
+	/// Returns the signature of `body`, as given in the signature header.
+	fn sign(&self, body: &[u8]) -> String {
+		let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).unwrap();
+		mac.update(body);
+		format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+	}
+
+	/// Sends the event with the given name and data to all webhooks, in the background.
+	pub fn send<T: Serialize>(&self, event: &'static str, data: &T) {
+		let date = Utc::now();
+		let body = json!({
+			"event": event,
+			"date": date,
+			"data": data,
+		})
+		.to_string();
+		let signature = self.sign(body.as_bytes());
+		for url in &self.urls {
+			let delivery = Delivery {
+				id: self.next_id.fetch_add(1, Ordering::Relaxed),
+				event,
+				url: url.clone(),
+				date,
+				attempts: 0,
+				status: None,
+				error: None,
+				delivered: false,
+			};
+			{
+				let mut log = self.log.lock().unwrap();
+				if log.len() >= MAX_LOG_ENTRIES {
+					log.pop_front();
+				}
+				log.push_back(delivery.clone());
+			}
+			let client = self.client.clone();
+			let body = body.clone();
+			let signature = signature.clone();
+			let log = self.log.clone();
+			tokio::spawn(async move {
+				deliver(client, delivery, body, signature, log).await;
+			});
+		}
+	}
+
+	/// Returns the latest deliveries, from the newest to the oldest.
+	pub fn report(&self) -> Vec<Delivery> {
+		self.log.lock().unwrap().iter().rev().cloned().collect()
+	}
+}
+
+/// Delivers an event, retrying on failure, and updates its state in `log`.
+async fn deliver(
+	client: reqwest::Client,
+	mut delivery: Delivery,
+	body: String,
+	signature: String,
+	log: Arc<Mutex<VecDeque<Delivery>>>,
+) {
+	let mut backoff = INITIAL_BACKOFF;
+	loop {
+		let res = client
+			.post(&delivery.url)
+			.timeout(TIMEOUT)
+			.header(EVENT_HEADER, delivery.event)
+			.header(DELIVERY_HEADER, delivery.id)
+			.header(SIGNATURE_HEADER, &signature)
+			.header("content-type", "application/json")
+			.body(body.clone())
+			.send()
+			.await;
+		delivery.attempts += 1;
+		delivery.status = res.as_ref().ok().map(|r| r.status().as_u16());
+		delivery.error = match res.and_then(|r| r.error_for_status()) {
+			Ok(_) => None,
+			Err(error) => Some(error.to_string()),
+		};
+		delivery.delivered = delivery.error.is_none();
+		if let Some(entry) = log.lock().unwrap().iter_mut().find(|d| d.id == delivery.id) {
+			*entry = delivery.clone();
+		}
+		let Some(error) = &delivery.error else {
+			break;
+		};
+		warn!(
+			%error,
+			url = delivery.url,
+			attempts = delivery.attempts,
+			"could not deliver webhook"
+		);
+		if delivery.attempts >= MAX_ATTEMPTS {
+			break;
+		}
+		tokio::time::sleep(backoff).await;
+		backoff *= 2;
+	}
+}
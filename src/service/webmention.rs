@@ -0,0 +1,524 @@
+//! This module implements [Webmention](https://www.w3.org/TR/webmention/), to be notified when
+//! another site links to an article, and to notify sites linked by articles.
+//!
+//! Received mentions are persisted so that they survive restarts.
+
+use crate::{
+	service::{article::Article, persist::JsonFile},
+	Context,
+};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use lol_html::{element, text, HtmlRewriter};
+use pulldown_cmark::{Event, Parser, Tag};
+use reqwest::{
+	dns::{Addrs, Name, Resolve, Resolving},
+	header::{CONTENT_TYPE, LINK},
+	redirect, Url,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::HashMap,
+	net::{IpAddr, Ipv4Addr},
+	path::Path,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::net::lookup_host;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// The timeout for requests to other sites.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// The maximum number of redirections followed when fetching a page.
+const MAX_REDIRECTS: usize = 5;
+/// The maximum size of a fetched page, in bytes.
+const MAX_PAGE_SIZE: usize = 1024 * 1024;
+/// The maximum number of mentions stored per article.
+const MAX_MENTIONS: usize = 100;
+/// The maximum length of the title of a mention, in characters.
+const MAX_TITLE_LEN: usize = 200;
+/// The name of the file holding received mentions.
+const MENTIONS_FILE: &str = "webmentions.json";
+
+/// A page linking to an article.
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+pub struct Mention {
+	/// The URL of the page.
+	pub source: String,
+	/// The title of the page, if any.
+	pub title: Option<String>,
+	/// The date at which the mention has been verified.
+	pub date: DateTime<Utc>,
+}
+
+/// The mentions of an article.
+#[derive(Default)]
+struct ArticleMentions {
+	/// Incremented each time the mentions change.
+	revision: u64,
+	/// The last time the mentions changed since the server started, if any.
+	modified: Option<DateTime<Utc>>,
+	/// The mentions, from the oldest to the newest.
+	list: Vec<Mention>,
+}
+
+/// Sends and receives webmentions.
+pub struct Webmentions {
+	/// The HTTP client used to fetch other sites.
+	client: reqwest::Client,
+	/// Tells whether webmentions are enabled.
+	enabled: bool,
+	/// Verified mentions, by article slug.
+	mentions: Mutex<HashMap<String, ArticleMentions>>,
+	/// The file in which mentions are persisted.
+	file: JsonFile,
+}
+
+impl Webmentions {
+	/// Creates a new instance.
+	///
+	/// If `enabled` is `false`, webmentions are neither sent nor received.
+	///
+	/// Mentions persisted in the directory `data_path` are loaded.
+	pub fn new(enabled: bool, data_path: &Path) -> Self {
+		let client = reqwest::Client::builder()
+			.timeout(TIMEOUT)
+			.dns_resolver(Arc::new(PublicResolver))
+			.redirect(redirect::Policy::custom(|attempt| {
+				if attempt.previous().len() >= MAX_REDIRECTS {
+					attempt.error("too many redirects")
+				} else if !is_public_host(attempt.url()) {
+					attempt.error("redirect to a private address")
+				} else {
+					attempt.follow()
+				}
+			}))
+			.build()
+			.unwrap();
+		let file = JsonFile::new(data_path, MENTIONS_FILE);
+		let mentions: HashMap<String, Vec<Mention>> = file.load().unwrap_or_default();
+		let mentions = mentions
+			.into_iter()
+			.map(|(slug, list)| {
+				(
+					slug,
+					ArticleMentions {
+						revision: 0,
+						modified: None,
+						list,
+					},
+				)
+			})
+			.collect();
+		Self {
+			client,
+			enabled,
+			mentions: Mutex::new(mentions),
+			file,
+		}
+	}
+
+	/// Tells whether webmentions are enabled.
+	pub fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	/// Returns the revision and the list of mentions of the article with the given slug.
+	///
+	/// The revision changes each time the list changes.
+	pub fn get(&self, slug: &str) -> (u64, Vec<Mention>) {
+		let mentions = self.mentions.lock().unwrap();
+		mentions
+			.get(slug)
+			.map(|m| (m.revision, m.list.clone()))
+			.unwrap_or_default()
+	}
+
+	/// Returns the last time the mentions of the article with the given slug changed since the
+	/// server started, if any.
+	pub fn last_modified(&self, slug: &str) -> Option<DateTime<Utc>> {
+		let mentions = self.mentions.lock().unwrap();
+		mentions.get(slug).and_then(|m| m.modified)
+	}
+
+	/// Returns the mentions, by article slug.
+	pub fn report(&self) -> HashMap<String, Vec<Mention>> {
+		let mentions = self.mentions.lock().unwrap();
+		mentions
+			.iter()
+			.filter(|(_, m)| !m.list.is_empty())
+			.map(|(slug, m)| (slug.clone(), m.list.clone()))
+			.collect()
+	}
+
+	/// Stores or updates the mention of the article with the given slug.
+	fn store(&self, slug: &str, mention: Mention) {
+		{
+			let mut mentions = self.mentions.lock().unwrap();
+			let article = mentions.entry(slug.to_owned()).or_default();
+			match article.list.iter().position(|m| m.source == mention.source) {
+				Some(i) => article.list[i] = mention,
+				None if article.list.len() < MAX_MENTIONS => article.list.push(mention),
+				None => return,
+			}
+			article.revision += 1;
+			article.modified = Some(Utc::now());
+		}
+		self.file.save(|| self.report());
+	}
+
+	/// Removes the mention of the article with the given slug by `source`, if any.
+	fn remove(&self, slug: &str, source: &str) {
+		{
+			let mut mentions = self.mentions.lock().unwrap();
+			let Some(article) = mentions.get_mut(slug) else {
+				return;
+			};
+			let len = article.list.len();
+			article.list.retain(|m| m.source != source);
+			if article.list.len() == len {
+				return;
+			}
+			article.revision += 1;
+			article.modified = Some(Utc::now());
+		}
+		self.file.save(|| self.report());
+	}
+
+	/// Fetches the HTML page at `url`. On success, the function returns the final URL of the
+	/// page, its `Link` headers and its content.
+	async fn fetch(&self, url: &str) -> Result<(Url, Vec<String>, String)> {
+		let url = Url::parse(url)?;
+		if !matches!(url.scheme(), "http" | "https") {
+			bail!("unsupported scheme");
+		}
+		if !is_public_host(&url) {
+			bail!("private address");
+		}
+		let mut response = self.client.get(url).send().await?.error_for_status()?;
+		let links = response
+			.headers()
+			.get_all(LINK)
+			.iter()
+			.filter_map(|h| h.to_str().ok())
+			.map(str::to_owned)
+			.collect();
+		let html = response
+			.headers()
+			.get(CONTENT_TYPE)
+			.and_then(|h| h.to_str().ok())
+			.is_some_and(|t| t.starts_with("text/html"));
+		let url = response.url().clone();
+		let mut body = vec![];
+		if html {
+			while let Some(chunk) = response.chunk().await? {
+				body.extend_from_slice(&chunk);
+				if body.len() > MAX_PAGE_SIZE {
+					bail!("page too large");
+				}
+			}
+		}
+		Ok((url, links, String::from_utf8_lossy(&body).into_owned()))
+	}
+}
+
+/// Tells whether the host of the given URL is not obviously private (such as `localhost` or a
+/// private IP address).
+fn is_public_host(url: &Url) -> bool {
+	let Some(host) = url.host_str() else {
+		return false;
+	};
+	let host = host.trim_start_matches('[').trim_end_matches(']');
+	match host.parse::<IpAddr>() {
+		Ok(ip) => is_public_ip(ip),
+		Err(_) => host != "localhost" && !host.ends_with(".localhost"),
+	}
+}
+
+/// Tells whether the given IPv4 address is reachable from the Internet.
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+	let [a, b, ..] = ip.octets();
+	// `0.0.0.0/8` and `100.64.0.0/10` (shared address space)
+	let reserved = a == 0 || (a == 100 && (b & 0xc0) == 64);
+	!reserved
+		&& !ip.is_private()
+		&& !ip.is_loopback()
+		&& !ip.is_link_local()
+		&& !ip.is_broadcast()
+		&& !ip.is_multicast()
+}
+
+/// Tells whether the given address is reachable from the Internet.
+fn is_public_ip(ip: IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(ip) => is_public_ipv4(ip),
+		IpAddr::V6(ip) => {
+			if let Some(ip) = ip.to_ipv4_mapped() {
+				return is_public_ipv4(ip);
+			}
+			let unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+			let link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+			!ip.is_loopback()
+				&& !ip.is_unspecified()
+				&& !ip.is_multicast()
+				&& !unique_local
+				&& !link_local
+		}
+	}
+}
+
+/// DNS resolver refusing names that resolve to non-public addresses, so that mentions cannot be
+/// used to issue requests to the server's network.
+///
+/// Since the client connects to the addresses checked here, a name cannot resolve to a public
+/// address when checked, then to a private one when connecting.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+	fn resolve(&self, name: Name) -> Resolving {
+		Box::pin(async move {
+			let addrs: Vec<_> = lookup_host((name.as_str(), 0)).await?.collect();
+			if addrs.is_empty() || !addrs.iter().all(|addr| is_public_ip(addr.ip())) {
+				return Err("name resolves to a private address".into());
+			}
+			let addrs: Addrs = Box::new(addrs.into_iter());
+			Ok(addrs)
+		})
+	}
+}
+
+/// Tells whether the URL of the webmention `target` points to a public article, and returns its
+/// slug.
+pub fn get_target_slug<'a>(ctx: &Context, target: &'a str) -> Option<&'a str> {
+	let path = target.strip_prefix(&ctx.base_url)?;
+	let slug = path.strip_prefix("/a/")?;
+	let slug = slug.split(['#', '?']).next()?.trim_end_matches('/');
+	ctx.get_article(slug)
+		.is_some_and(|(a, _)| a.is_public())
+		.then_some(slug)
+}
+
+/// Verifies that the page at `source` links to `target`, the URL of the article with the given
+/// slug, then stores the mention.
+///
+/// If the page does not link to the article anymore, the mention is removed.
+pub async fn receive(ctx: Arc<Context>, slug: String, source: String, target: String) {
+	let webmentions = &ctx.webmentions;
+	let (_, _, page) = match webmentions.fetch(&source).await {
+		Ok(page) => page,
+		Err(error) => {
+			warn!(%error, source, "could not fetch webmention source");
+			if error
+				.downcast_ref::<reqwest::Error>()
+				.and_then(reqwest::Error::status)
+				.is_some_and(|s| s.as_u16() == 404 || s.as_u16() == 410)
+			{
+				webmentions.remove(&slug, &source);
+			}
+			return;
+		}
+	};
+	let mut linked = false;
+	let mut title = String::new();
+	let mut rewriter = HtmlRewriter::new(
+		lol_html::Settings {
+			element_content_handlers: vec![
+				element!("a[href], img[src], video[src], audio[src]", |e| {
+					let url = e.get_attribute("href").or_else(|| e.get_attribute("src"));
+					linked |= url.as_deref() == Some(target.as_str());
+					Ok(())
+				}),
+				text!("head > title", |t| {
+					title.push_str(t.as_str());
+					Ok(())
+				}),
+			],
+			..lol_html::Settings::default()
+		},
+		|_: &[u8]| {},
+	);
+	if rewriter
+		.write(page.as_bytes())
+		.and_then(|_| rewriter.end())
+		.is_err()
+	{
+		warn!(source, "could not parse webmention source");
+		return;
+	}
+	if !linked {
+		info!(source, target, "webmention source does not link to target");
+		webmentions.remove(&slug, &source);
+		return;
+	}
+	let title = html_escape::decode_html_entities(&title);
+	let title = title.trim();
+	let title = (!title.is_empty()).then(|| title.chars().take(MAX_TITLE_LEN).collect());
+	info!(source, target, "webmention received");
+	webmentions.store(
+		&slug,
+		Mention {
+			source,
+			title,
+			date: Utc::now(),
+		},
+	);
+}
+
+/// Returns the absolute URLs of the external pages linked by the given article.
+fn get_links(article: &Article) -> Vec<String> {
+	let mut links = vec![];
+	for event in Parser::new(&article.markdown) {
+		let Event::Start(Tag::Link { dest_url, .. }) = event else {
+			continue;
+		};
+		let url = dest_url.strip_prefix('_').unwrap_or(&dest_url);
+		let external = url.starts_with("http://") || url.starts_with("https://");
+		if external && !links.iter().any(|l| l == url) {
+			links.push(url.to_owned());
+		}
+	}
+	links
+}
+
+/// Returns the URL of the Webmention endpoint of the page at `target`, if any.
+async fn discover(webmentions: &Webmentions, target: &str) -> Result<Option<Url>> {
+	let (url, links, page) = webmentions.fetch(target).await?;
+	// The `Link` header takes precedence over the content
+	let header_endpoint = links.iter().flat_map(|h| h.split(',')).find_map(|link| {
+		let (href, params) = link.split_once(';')?;
+		let href = href.trim().strip_prefix('<')?.strip_suffix('>')?;
+		let is_webmention = params.split(';').any(|p| {
+			let Some((name, value)) = p.split_once('=') else {
+				return false;
+			};
+			name.trim() == "rel"
+				&& value
+					.trim()
+					.trim_matches('"')
+					.split_whitespace()
+					.any(|r| r == "webmention")
+		});
+		is_webmention.then(|| href.to_owned())
+	});
+	let mut endpoint = header_endpoint;
+	if endpoint.is_none() {
+		let mut rewriter = HtmlRewriter::new(
+			lol_html::Settings {
+				element_content_handlers: vec![element!(
+					"link[rel~=webmention][href], a[rel~=webmention][href]",
+					|e| {
+						if endpoint.is_none() {
+							endpoint = e.get_attribute("href");
+						}
+						Ok(())
+					}
+				)],
+				..lol_html::Settings::default()
+			},
+			|_: &[u8]| {},
+		);
+		rewriter.write(page.as_bytes())?;
+		rewriter.end()?;
+	}
+	Ok(endpoint.map(|e| url.join(&e)).transpose()?)
+}
+
+/// Sends a webmention to each page linked by the article with the given slug.
+pub async fn send(ctx: Arc<Context>, slug: String) {
+	let Some((article, _)) = ctx.get_article(&slug) else {
+		return;
+	};
+	let source = article.get_url(&ctx.base_url);
+	for target in get_links(article) {
+		let endpoint = match discover(&ctx.webmentions, &target).await {
+			Ok(Some(endpoint)) => endpoint,
+			Ok(None) => continue,
+			Err(error) => {
+				warn!(%error, target, "could not discover webmention endpoint");
+				continue;
+			}
+		};
+		if !is_public_host(&endpoint) {
+			warn!(target, "webmention endpoint has a private address");
+			continue;
+		}
+		let res = ctx
+			.webmentions
+			.client
+			.post(endpoint)
+			.form(&[("source", source.as_str()), ("target", target.as_str())])
+			.send()
+			.await
+			.and_then(|r| r.error_for_status());
+		match res {
+			Ok(_) => info!(source, target, "webmention sent"),
+			Err(error) => warn!(%error, target, "could not send webmention"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn public_ip() {
+		for ip in ["8.8.8.8", "2001:4860:4860::8888", "::ffff:8.8.8.8"] {
+			assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+		}
+	}
+
+	#[test]
+	fn private_ip() {
+		for ip in [
+			"0.0.0.0",
+			"10.0.0.1",
+			"100.64.0.1",
+			"127.0.0.1",
+			"169.254.169.254",
+			"172.16.0.1",
+			"192.168.1.1",
+			"255.255.255.255",
+			"::",
+			"::1",
+			"fc00::1",
+			"fd12:3456::1",
+			"fe80::1",
+			"ff02::1",
+		] {
+			assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+		}
+	}
+
+	#[test]
+	fn mapped_private_ip() {
+		for ip in [
+			"::ffff:127.0.0.1",
+			"::ffff:10.0.0.1",
+			"::ffff:169.254.169.254",
+		] {
+			assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+		}
+	}
+
+	#[test]
+	fn private_host() {
+		for url in [
+			"http://localhost/",
+			"http://blog.localhost/",
+			"http://127.0.0.1/",
+			"http://[::1]/",
+			"http://[::ffff:127.0.0.1]/",
+		] {
+			assert!(!is_public_host(&Url::parse(url).unwrap()), "{url}");
+		}
+		assert!(is_public_host(&Url::parse("https://example.com/").unwrap()));
+	}
+
+	#[tokio::test]
+	async fn resolver_rejects_loopback() {
+		let name = "localhost".parse().unwrap();
+		assert!(PublicResolver.resolve(name).await.is_err());
+	}
+}
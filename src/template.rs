@@ -0,0 +1,199 @@
+//! Page templates, checked at compile time.
+//!
+//! Values are HTML-escaped unless marked with the `safe` filter in the template.
+
+use crate::{
+	route::theme::Theme,
+	service::{
+		article::{Article, ArticleListHtml, ArticleSearchHtml, ArticleSort, Author},
+		i18n::Locale,
+		reaction::Reaction,
+		webmention::Mention,
+	},
+};
+use askama::Template;
+use axum::http::StatusCode;
+
+#[derive(Template)]
+#[template(path = "index.html")]
+pub struct IndexTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The URL of the blog.
+	pub base_url: &'a str,
+	/// The URL to the Discord server's invitation.
+	pub discord: &'a str,
+	/// The URL to the gateway.
+	pub gateway: &'a str,
+	/// The list of articles.
+	pub articles: Vec<ArticleListHtml<'a>>,
+}
+
+#[derive(Template)]
+#[template(path = "bio.html")]
+pub struct BioTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The URL of the blog.
+	pub base_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "legal.html")]
+pub struct LegalTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The URL of the blog.
+	pub base_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "search.html")]
+pub struct SearchTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The searched text.
+	pub q: &'a str,
+	/// The tag to filter on.
+	pub tag: &'a str,
+	/// The year to filter on.
+	pub year: Option<i32>,
+	/// The order of the articles.
+	pub sort: ArticleSort,
+	/// The articles matching the search, from the most to the least relevant.
+	pub articles: Vec<ArticleSearchHtml<'a>>,
+}
+
+#[derive(Template)]
+#[template(path = "author.html")]
+pub struct AuthorTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The author.
+	pub author: &'a Author,
+	/// The author's name along with their avatar, in HTML.
+	pub author_html: String,
+	/// The articles written by the author.
+	pub articles: Vec<ArticleListHtml<'a>>,
+}
+
+#[derive(Template)]
+#[template(path = "article.html")]
+pub struct ArticleTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The article.
+	pub article: &'a Article,
+	/// The compiled content of the article, in HTML.
+	pub content: &'a str,
+	/// The absolute URL to the article.
+	pub url: String,
+	/// The comma-separated list of tags.
+	pub tags: String,
+	/// The post date, in RFC 3339 format.
+	pub date: String,
+	/// The list of authors, in HTML.
+	pub authors: String,
+	/// The notice of the article's license, in HTML.
+	pub license: Option<String>,
+	/// The structured data of the article, in JSON-LD format.
+	pub json_ld: String,
+	/// The URL to the Discord server's invitation.
+	pub discord: &'a str,
+	/// The URL of the Webmention endpoint, if webmentions are enabled.
+	pub webmention_endpoint: Option<String>,
+	/// The pages mentioning the article.
+	pub mentions: Vec<Mention>,
+	/// The reactions to the article, along with their number.
+	pub reactions: Vec<(Reaction, u64)>,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+pub struct ErrorTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The status of the response.
+	pub status: StatusCode,
+	/// The identifier of the request, to be given when reporting the error.
+	pub reference: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "error_404.html")]
+pub struct NotFoundTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The status of the response.
+	pub status: StatusCode,
+	/// The identifier of the request, to be given when reporting the error.
+	pub reference: &'a str,
+	/// Articles whose slug is close to the requested path.
+	pub suggestions: Vec<&'a Article>,
+}
+
+#[derive(Template)]
+#[template(path = "error_429.html")]
+pub struct TooManyRequestsTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The status of the response.
+	pub status: StatusCode,
+	/// The identifier of the request, to be given when reporting the error.
+	pub reference: &'a str,
+	/// The number of seconds to wait before retrying, if known.
+	pub retry_after: Option<u64>,
+}
+
+#[derive(Template)]
+#[template(path = "error_5xx.html")]
+pub struct ServerErrorTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The status of the response.
+	pub status: StatusCode,
+	/// The identifier of the request, to be given when reporting the error.
+	pub reference: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "admin_editor.html")]
+pub struct AdminEditorTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+	/// The names of the article directories.
+	pub articles: Vec<String>,
+	/// The name of the edited article directory, if any.
+	pub name: Option<&'a str>,
+	/// The manifest of the edited article.
+	pub manifest: String,
+	/// The content of the edited article, in Markdown.
+	pub content: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin_login.html")]
+pub struct AdminLoginTemplate<'a> {
+	/// The color theme of the page.
+	pub theme: Theme,
+	/// The locale of the page.
+	pub t: Locale<'a>,
+}
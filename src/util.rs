@@ -0,0 +1,147 @@
+//! Utility functions.
+
+use axum::http::{
+	header::{COOKIE, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+	HeaderMap,
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::{
+	fs,
+	hash::{DefaultHasher, Hash, Hasher},
+	io,
+	net::{IpAddr, SocketAddr},
+	path::{Path, PathBuf},
+};
+
+/// Tells whether the secrets `a` and `b` are equal, in a time that does not depend on their
+/// content, to prevent timing attacks.
+pub fn secret_eq(a: &str, b: &str) -> bool {
+	// Hashing first makes the comparison independent of the secrets' lengths
+	let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+	a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Returns a strong entity tag for the given content.
+pub fn etag(content: &[u8]) -> String {
+	let mut hasher = DefaultHasher::new();
+	content.hash(&mut hasher);
+	format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Formats the given date for use in HTTP headers.
+pub fn http_date(date: &DateTime<Utc>) -> String {
+	date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Tells whether the client's cached copy of a resource is still fresh, according to the
+/// conditional headers of the request.
+///
+/// `etag` is the current entity tag of the resource and `last_modified` is the date of its last
+/// modification.
+pub fn is_fresh(headers: &HeaderMap, etag: &str, last_modified: &DateTime<Utc>) -> bool {
+	// `If-None-Match` takes precedence over `If-Modified-Since`
+	if let Some(tags) = headers.get(IF_NONE_MATCH) {
+		let Ok(tags) = tags.to_str() else {
+			return false;
+		};
+		return tags
+			.split(',')
+			.map(|t| t.trim())
+			.any(|t| t == "*" || t.strip_prefix("W/").unwrap_or(t) == etag);
+	}
+	headers
+		.get(IF_MODIFIED_SINCE)
+		.and_then(|h| h.to_str().ok())
+		.and_then(|h| DateTime::parse_from_rfc2822(h).ok())
+		.is_some_and(|since| last_modified.timestamp() <= since.timestamp())
+}
+
+/// Returns the IP address of the client which issued a request.
+///
+/// If the connection's peer `addr` is one of the `trusted_proxies`, the address is taken from the
+/// `X-Forwarded-For` header. Else, the header may be forged by the client, so the peer's address
+/// is used.
+///
+/// The last address of the header is used since it is the one appended by the reverse proxy,
+/// while the previous ones may be forged by the client.
+pub fn client_ip(trusted_proxies: &[IpAddr], headers: &HeaderMap, addr: SocketAddr) -> IpAddr {
+	if !trusted_proxies.contains(&addr.ip()) {
+		return addr.ip();
+	}
+	headers
+		.get("X-Forwarded-For")
+		.and_then(|h| h.to_str().ok())
+		.and_then(|h| h.rsplit(',').next())
+		.and_then(|ip| ip.trim().parse().ok())
+		.unwrap_or(addr.ip())
+}
+
+/// Returns the paths of all files in the given directory, relative to `root`.
+pub fn list_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+	for e in fs::read_dir(dir)? {
+		let e = e?;
+		let path = e.path();
+		if e.file_type()?.is_dir() {
+			list_files(root, &path, files)?;
+		} else if let Ok(path) = path.strip_prefix(root) {
+			files.push(path.to_path_buf());
+		}
+	}
+	Ok(())
+}
+
+/// Returns the value of the cookie with the given `name`, if present in `headers`.
+pub fn get_cookie<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+	headers
+		.get_all(COOKIE)
+		.iter()
+		.filter_map(|h| h.to_str().ok())
+		.flat_map(|h| h.split(';'))
+		.filter_map(|c| c.trim().split_once('='))
+		.find(|(n, _)| *n == name)
+		.map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PROXY: &str = "127.0.0.1";
+
+	fn headers(forwarded_for: &str) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		headers.insert("X-Forwarded-For", forwarded_for.parse().unwrap());
+		headers
+	}
+
+	#[test]
+	fn client_ip_untrusted_peer() {
+		let trusted = [PROXY.parse().unwrap()];
+		let addr: SocketAddr = "203.0.113.1:1234".parse().unwrap();
+		let ip = client_ip(&trusted, &headers("198.51.100.1"), addr);
+		assert_eq!(ip, addr.ip());
+	}
+
+	#[test]
+	fn client_ip_spoofed_chain() {
+		let trusted = [PROXY.parse().unwrap()];
+		// The client forged the first address, the proxy appended the last one
+		let headers = headers("10.0.0.1, 198.51.100.1");
+		let addr = SocketAddr::new(PROXY.parse().unwrap(), 1234);
+		let ip = client_ip(&trusted, &headers, addr);
+		assert_eq!(ip, "198.51.100.1".parse::<IpAddr>().unwrap());
+		// A forged chain sent directly to the server is ignored
+		let addr: SocketAddr = "203.0.113.1:1234".parse().unwrap();
+		assert_eq!(client_ip(&trusted, &headers, addr), addr.ip());
+	}
+
+	#[test]
+	fn client_ip_invalid_header() {
+		let trusted = [PROXY.parse().unwrap()];
+		let addr = SocketAddr::new(PROXY.parse().unwrap(), 1234);
+		let ip = client_ip(&trusted, &headers("not an address"), addr);
+		assert_eq!(ip, addr.ip());
+		assert_eq!(client_ip(&trusted, &HeaderMap::new(), addr), addr.ip());
+	}
+}